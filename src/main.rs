@@ -1,372 +1,6098 @@
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
+use bevy::render::view::screenshot::{Screenshot, ScreenshotCaptured};
+use bevy::time::{Stopwatch, TimeUpdateStrategy};
+use bevy::window::{PrimaryWindow, WindowFocused, WindowMode, WindowResized};
+use serde::{Deserialize, Serialize};
 
-// Defines the radius in the center of the screen where automovers cannot spawn
-const FREE_ZONE: f32 = 200.0;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .insert_resource(Speed(100.0))
-        .insert_resource(Score(0))
-        .insert_resource(Lives(3))
-        .add_event::<CollisionWithPresentEvent>()
-        .add_event::<CollisionWithSnowflakeEvent>()
-        .add_systems(Startup, (
-            setup_camera,
-            initialize_automovers::<Present, 10>,
-            initialize_automovers::<Snowflake, 10>,
-            initialize_santa,
-            initialize_ui,
-        ))
-        .add_systems(Update, (
-            automoving_system,
-            bounce_automovers_system,
-            move_santa_system,
-            detect_collisions_system::<Present, CollisionWithPresentEvent>,
-            detect_collisions_system::<Snowflake, CollisionWithSnowflakeEvent>,
-            score_points_system.run_if(on_event::<CollisionWithPresentEvent>),
-            update_score_ui.run_if(resource_changed::<Score>),
-            take_lives_system.run_if(on_event::<CollisionWithSnowflakeEvent>),
-            update_lives_ui.run_if(resource_changed::<Lives>),
-            speed_up_on_score.run_if(on_event::<CollisionWithPresentEvent>),
-        ))
-        .add_systems(PostUpdate, (
-            remove_entity_on_collission_system::<CollisionWithPresentEvent>,
-            remove_entity_on_collission_system::<CollisionWithSnowflakeEvent>,
-            win_system,
-            loose_system.run_if(resource_changed::<Lives>),
+    let mut app = App::new();
+    // Command line options are read here, so the plugin itself never looks at the process arguments
+    if let Some(playback) = arg_value("--replay-input").and_then(|path| load_input_playback(&path)) {
+        app.insert_resource(playback);
+    }
+    if let Some(seed) = arg_value("--seed") {
+        match seed.parse() {
+            Ok(seed) => {
+                app.insert_resource(GameRng::with_seed(seed));
+            }
+            Err(error) => eprintln!("Ignoring the invalid seed {}: {}", seed, error),
+        }
+    }
+    if let Some(target) = arg_value("--target-score") {
+        match target.parse() {
+            Ok(0) => eprintln!("Ignoring the target score 0, the run would be won before it starts"),
+            Ok(target) => {
+                app.insert_resource(WinCondition::ReachScore(target));
+            }
+            Err(error) => eprintln!("Ignoring the invalid target score {}: {}", target, error),
+        }
+    }
+    let mut primary_window = Window::default();
+    if let Some(size) = arg_value("--window-size") {
+        match parse_window_size(&size) {
+            Ok(size) => primary_window.resolution = size.into(),
+            Err(error) => eprintln!("Ignoring --window-size {}: {}", size, error),
+        }
+    }
+    app
+        .insert_resource(InputRecorder { recording: std::env::args().any(|arg| arg == "--record-input"), ..default() })
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin { primary_window: Some(primary_window), ..default() }),
+            SantaGamePlugin,
         ))
         .run();
 }
 
-fn setup_camera(
-    mut commands: Commands,
-    windows: Query<&Window, With<PrimaryWindow>>,
-) {
-    let primary_window = windows.get_single().unwrap();
-    commands.spawn((
-        Camera2d,
-        Transform::from_xyz(primary_window.width() / 2.0, primary_window.height() / 2.0, 0.0),
-    ));
+// Anything smaller leaves no room to play around the sleigh and the HUD
+const MIN_WINDOW_SIZE: Vec2 = Vec2::new(320.0, 240.0);
+
+// Parses `WIDTHxHEIGHT`, e.g. `1024x768`
+fn parse_window_size(value: &str) -> Result<Vec2, String> {
+    let (width, height) = value.split_once('x').ok_or("expected WIDTHxHEIGHT, e.g. 1024x768")?;
+    let width: f32 = width.trim().parse().map_err(|_| format!("invalid width '{}'", width))?;
+    let height: f32 = height.trim().parse().map_err(|_| format!("invalid height '{}'", height))?;
+    if width < MIN_WINDOW_SIZE.x || height < MIN_WINDOW_SIZE.y {
+        return Err(format!("the window must be at least {}x{}", MIN_WINDOW_SIZE.x, MIN_WINDOW_SIZE.y));
+    }
+    Ok(Vec2::new(width, height))
 }
 
-// Trait to define the sprite path, so we can use it in generic systems
-trait HasSpritePath {
-    fn sprite_path() -> &'static str;
+// The whole game: every resource, event, state and system, ready to be added next to `DefaultPlugins`
+pub struct SantaGamePlugin;
+
+impl Plugin for SantaGamePlugin {
+    fn build(&self, app: &mut App) {
+        let difficulty = Difficulty::default();
+        app
+            .insert_resource(difficulty)
+            .init_resource::<GameMode>()
+            .init_resource::<WinCondition>()
+            .insert_resource(Speed(difficulty.speed()))
+            .insert_resource(SantaSpeed(100.0))
+            .insert_resource(Acceleration(800.0))
+            .insert_resource(Friction(600.0))
+            .insert_resource(Score(0))
+            .insert_resource(StartingLives(difficulty.lives()))
+            .insert_resource(FreeZone(difficulty.free_zone()))
+            .insert_resource(Lives(difficulty.lives()))
+            .insert_resource(HighScore(load_high_score()))
+            .insert_resource(BestGrade(load_best_grade()))
+            .insert_resource(RunGrade(None))
+            .insert_resource(HighScoreBeaten(false))
+            .insert_resource(Level(1))
+            .insert_resource(FinalLevel(3))
+            .insert_resource(CollisionFudge(1.0))
+            .init_resource::<SpatialGrid>()
+            .insert_resource(AutomoverCollisions(true))
+            .init_resource::<RunStats>()
+            .init_resource::<Combo>()
+            .init_resource::<NewGamePlus>()
+            .init_resource::<ScreenShake>()
+            .init_resource::<PickupSpawner<HeartPickup>>()
+            .init_resource::<PickupSpawner<ShieldPickup>>()
+            .init_resource::<PickupSpawner<SlowMoPickup>>()
+            .init_resource::<SlowMo>()
+            .init_resource::<PickupSpawner<FreezePickup>>()
+            .init_resource::<Frozen>()
+            .init_resource::<PickupSpawner<MagnetPickup>>()
+            .init_resource::<PickupSpawner<BoostPickup>>()
+            .init_resource::<PickupSpawner<BombPickup>>()
+            .init_resource::<PickupSpawner<ReindeerPickup>>()
+            .init_resource::<StreakProgress>()
+            .init_resource::<SnowmanSpawned>()
+            .init_resource::<SleighHazardTimer>()
+            .init_resource::<HomingConverted>()
+            .init_resource::<GameRng>()
+            .insert_resource(GameTimer(Stopwatch::new()))
+            .insert_resource(SnowflakeSpawner::new(difficulty.spawn_interval()))
+            .insert_resource(MaxSnowflakes(difficulty.snowflakes()))
+            .insert_resource(RoundTimer(Timer::from_seconds(TIME_ATTACK_SECONDS, TimerMode::Once)))
+            .insert_resource(Countdown(Timer::from_seconds(COUNTDOWN_SECONDS, TimerMode::Once)))
+            .add_event::<CollisionEvent>()
+            .init_state::<GameState>()
+            .add_sub_state::<PauseState>()
+            .enable_state_scoped_entities::<GameState>()
+            .enable_state_scoped_entities::<PauseState>()
+            .insert_resource(Muted(false))
+            .init_resource::<GameBounds>()
+            .add_systems(PreStartup, sync_bounds_system)
+            .add_systems(Startup, (
+                setup_camera,
+                load_sound_effects,
+                load_ui_images,
+                setup_music,
+            ))
+            .add_systems(Update, toggle_mute_system)
+            .add_systems(Update, camera_shake_system)
+            .init_resource::<MovementKeys>()
+            .init_resource::<PlayerInput>()
+            .init_resource::<InputRecorder>()
+            .add_systems(Update, toggle_input_recording_system)
+            .add_systems(Last, save_input_trace_system.run_if(on_event::<AppExit>))
+            .init_resource::<CursorWorldPosition>()
+            .init_resource::<FollowCursor>()
+            .add_systems(Update, (
+                toggle_follow_cursor_system,
+                update_follow_cursor_ui.run_if(resource_changed::<FollowCursor>),
+            ).chain())
+            .init_resource::<ScreenshotCounter>()
+            .add_systems(Update, (take_screenshot_system, toast_system))
+            .add_systems(Update, (
+                toggle_fullscreen_system,
+                (
+                    sync_bounds_system,
+                    recenter_camera_system,
+                    fit_to_window_system,
+                ).chain().run_if(on_event::<WindowResized>),
+            ).chain())
+            .init_resource::<InputBindings>()
+            .init_resource::<GamepadDeadzone>()
+            .init_resource::<TouchJoystick>()
+            .init_resource::<Rebinding>()
+            .init_resource::<TutorialStep>()
+            .init_resource::<BestScore>()
+            .init_resource::<NewRecord>()
+            .add_systems(OnEnter(GameState::Settings), initialize_settings)
+            .add_systems(Update, (
+                settings_button_system,
+                rebind_system,
+            ).chain().run_if(in_state(GameState::Settings)))
+            .add_systems(Update, pause_rebind_system
+                .before(toggle_pause_system)
+                .before(restart_game_system)
+                .before(toggle_mute_system)
+                .run_if(in_state(PauseState::Paused)))
+            .add_systems(Update, update_binding_labels
+                .after(rebind_system)
+                .after(pause_rebind_system)
+                .run_if(resource_changed::<InputBindings>.or(resource_changed::<Rebinding>)))
+            .insert_resource(DemoMode(false))
+            .insert_resource(MenuIdleTimer(Timer::from_seconds(MENU_IDLE_SECONDS, TimerMode::Once)))
+            .add_systems(OnEnter(GameState::Menu), (
+                cleanup_run_system,
+                initialize_menu,
+                reset_menu_idle_timer_system,
+            ))
+            .add_systems(Update, (
+                start_game_system,
+                menu_button_system,
+                select_difficulty_system,
+                select_game_mode_system,
+                start_demo_system,
+            ).run_if(in_state(GameState::Menu)))
+            .add_systems(Update, (
+                cancel_demo_system,
+                end_demo_system.run_if(in_game_over),
+            ).run_if(in_demo))
+            .add_systems(Update, button_hover_system)
+            .add_systems(Update, (
+                sync_starting_lives_system,
+                sync_free_zone_system,
+            ).run_if(resource_changed::<Difficulty>))
+            .add_systems(OnEnter(GameState::Countdown), (
+                cleanup_run_system,
+                reset_run_system,
+                reset_pickup_spawner_system::<HeartPickup>,
+                reset_pickup_spawner_system::<ShieldPickup>,
+                reset_pickup_spawner_system::<SlowMoPickup>,
+                reset_slow_mo_system,
+                reset_pickup_spawner_system::<FreezePickup>,
+                reset_frozen_system,
+                reset_pickup_spawner_system::<MagnetPickup>,
+                reset_pickup_spawner_system::<BoostPickup>,
+                reset_pickup_spawner_system::<BombPickup>,
+                reset_pickup_spawner_system::<ReindeerPickup>,
+                reset_snowman_system,
+                reset_homing_system,
+                reset_sleigh_hazard_system,
+                apply_new_game_plus_system,
+                (
+                    initialize_presents.run_if(not(resource_equals(GameMode::Survival))),
+                    initialize_santa,
+                    initialize_touch_joystick,
+                    initialize_sleigh.run_if(not(resource_equals(GameMode::Survival))),
+                    initialize_candy_canes.run_if(not(resource_equals(GameMode::Survival))),
+                    initialize_ice_patches,
+                    initialize_ui,
+                    initialize_countdown_ui,
+                    initialize_demo_ui.run_if(in_demo),
+                    initialize_tutorial_ui.run_if(tutorial_active.and(not(in_demo))),
+                ),
+                // After everything else is placed, so the trees can keep clear of it
+                initialize_trees,
+            ).chain())
+            .add_systems(Update, countdown_system.run_if(in_state(GameState::Countdown)))
+            .add_systems(OnEnter(PauseState::Paused), initialize_pause_ui)
+            .add_systems(Update, toggle_pause_system.run_if(not(in_demo)))
+            .add_systems(Update, pause_on_focus_loss_system
+                .after(toggle_pause_system)
+                .run_if(in_state(PauseState::Running).and(not(in_demo))))
+            .add_systems(OnEnter(GameState::Playing), pause_if_unfocused_system.run_if(not(in_demo)))
+            .add_systems(Update, (
+                (
+                    (
+                        tick_timer_system,
+                        survival_score_system.run_if(resource_equals(GameMode::Survival)),
+                    ).chain(),
+                    (
+                        tick_round_timer_system,
+                        update_round_timer_ui,
+                    ).chain().run_if(resource_equals(GameMode::TimeAttack)),
+                    hide_countdown_text_system,
+                    tick_combo_system,
+                    (
+                        spin_system,
+                        particle_system,
+                        lifetime_system,
+                        animate_floating_text_system,
+                        invulnerability_system,
+                        shield_break_system,
+                        draw_magnet_system,
+                        edge_indicator_system,
+                    ),
+                    (
+                        tick_slow_mo_system,
+                        tick_frozen_system,
+                        homing_system.run_if(not(snowflakes_frozen)),
+                        magnet_system,
+                        snowman_seek_system,
+                        automoving_system,
+                        bounce_automovers_system,
+                        bounce_off_trees_system,
+                    ).chain(),
+                    // The keyboard reader starts the frame's input over, the other devices add to it.
+                    // A played back trace stands in for every live device.
+                    (
+                        (
+                            read_movement_keys_system,
+                            track_cursor_system,
+                            set_move_target_system,
+                        ).chain().run_if(not(resource_exists::<InputPlayback>)),
+                        play_back_input_system.run_if(resource_exists::<InputPlayback>),
+                        record_input_system.run_if(recording_input),
+                        read_keyboard_input_system,
+                        (
+                            read_gamepad_input_system,
+                            (
+                                track_touch_joystick_system,
+                                update_touch_joystick_marker_system,
+                                read_touch_input_system,
+                            ).chain(),
+                        ).chain().run_if(not(resource_exists::<InputPlayback>)),
+                    ).chain().before(move_santa_system).before(dash_system).run_if(not(in_demo)),
+                    dash_system.before(move_santa_system).run_if(not(in_demo)),
+                    (
+                        tick_speed_modifier_system,
+                        update_boost_ui,
+                    ).chain().before(move_santa_system),
+                    move_santa_system.run_if(not(in_demo).and(not(santa_is_dashing))),
+                    update_dash_ui.after(dash_system),
+                    update_stamina_ui.after(move_santa_system),
+                    update_move_target_marker_system.after(move_santa_system),
+                    demo_santa_system.run_if(in_demo),
+                    apply_velocity_system.after(move_santa_system).after(demo_santa_system),
+                    santa_tree_collision_system.after(apply_velocity_system),
+                    reindeer_system.after(santa_tree_collision_system),
+                    (
+                        spawn_snowflake_over_time_system,
+                        // Lives only matter outside of time attack, where hits cost time instead
+                        spawn_pickup_over_time_system::<HeartPickup>.run_if(not(resource_equals(GameMode::TimeAttack))),
+                        spawn_pickup_over_time_system::<ShieldPickup>,
+                        spawn_pickup_over_time_system::<SlowMoPickup>,
+                        spawn_pickup_over_time_system::<FreezePickup>,
+                        spawn_pickup_over_time_system::<MagnetPickup>,
+                        spawn_pickup_over_time_system::<BoostPickup>,
+                        spawn_pickup_over_time_system::<BombPickup>,
+                        // The reindeer only helps with presents, which survival does not have
+                        spawn_pickup_over_time_system::<ReindeerPickup>.run_if(not(resource_equals(GameMode::Survival))),
+                        spawn_snowman_system.run_if(resource_changed::<Score>),
+                        convert_homing_snowflakes_system.run_if(resource_changed::<Score>),
+                        relocate_expired_presents_system,
+                        (
+                            spawn_sleigh_hazard_warning_system,
+                            sleigh_hazard_warning_system,
+                            move_sleigh_hazard_system,
+                        ).chain(),
+                    ),
+                ),
+                (
+                    build_spatial_grid_system,
+                    (
+                        detect_collisions_system::<Present>.run_if(santa_has_room),
+                        detect_collisions_system::<GoldPresent>.run_if(santa_has_room),
+                        detect_collisions_system::<Sleigh>.run_if(santa_is_carrying),
+                        detect_collisions_system::<ExitPoint>,
+                        detect_collisions_system::<Snowflake>.run_if(not(santa_is_invulnerable)),
+                        detect_collisions_system::<Snowman>.run_if(not(santa_is_invulnerable)),
+                        detect_collisions_system::<SleighHazard>.run_if(not(santa_is_invulnerable)),
+                        detect_collisions_system::<HeartPickup>,
+                        detect_collisions_system::<ShieldPickup>,
+                        detect_collisions_system::<SlowMoPickup>,
+                        detect_collisions_system::<FreezePickup>,
+                        detect_collisions_system::<MagnetPickup>,
+                        detect_collisions_system::<BoostPickup>,
+                        detect_collisions_system::<BombPickup>,
+                        detect_collisions_system::<CandyCane>,
+                        detect_collisions_system::<ReindeerPickup>,
+                        // Frozen snowflakes stand still, nothing should knock them off their course meanwhile
+                        automover_collision_system.run_if(resource_equals(AutomoverCollisions(true)).and(not(snowflakes_frozen))),
+                    ),
+                ).chain(),
+                // Collisions are handled in the same frame they are detected, so pausing never leaves events pending
+                (
+                    pick_up_presents_system.run_if(on_present_collision),
+                    score_points_system.run_if(on_collision(CollisionKind::Sleigh)),
+                    update_carrying_ui.after(pick_up_presents_system).after(score_points_system),
+                    update_score_ui.run_if(resource_changed::<Score>),
+                    update_combo_ui.after(pick_up_presents_system),
+                    (
+                        collect_candy_canes_system.run_if(on_collision(CollisionKind::CandyCane)),
+                        update_candy_cane_ui.run_if(resource_changed::<StreakProgress>),
+                    ).chain(),
+                    // Must see the last points of a run before it ends in PostUpdate
+                    (
+                        update_high_score_system,
+                        update_high_score_ui,
+                    ).chain().after(score_points_system).run_if(resource_changed::<Score>.and(not(in_demo))),
+                    update_level_ui.run_if(resource_changed::<Level>),
+                    update_timer_ui,
+                    (
+                        take_lives_system.run_if(
+                            on_collision(CollisionKind::Snowflake)
+                                .or(on_collision(CollisionKind::Snowman))
+                                .or(on_collision(CollisionKind::SleighHazard))
+                        ),
+                        heal_system.run_if(on_collision(CollisionKind::HeartPickup)),
+                        grant_shield_system.run_if(on_collision(CollisionKind::ShieldPickup)),
+                        update_shield_ui,
+                        start_slow_mo_system.run_if(on_collision(CollisionKind::SlowMoPickup)),
+                        start_frozen_system.run_if(on_collision(CollisionKind::FreezePickup)),
+                        grant_magnet_system.run_if(on_collision(CollisionKind::MagnetPickup)),
+                        grant_speed_boost_system.run_if(on_collision(CollisionKind::BoostPickup)),
+                        detonate_bomb_system.run_if(on_collision(CollisionKind::BombPickup)),
+                        spawn_reindeer_system.run_if(on_collision(CollisionKind::ReindeerPickup)),
+                        update_lives_ui.run_if(resource_changed::<Lives>),
+                    ).chain(),
+                    speed_up_on_score.run_if(on_present_collision),
+                    respawn_presents_system.run_if(on_present_collision.and(respawns_presents)),
+                    track_run_stats_system.after(speed_up_on_score),
+                    (
+                        advance_tutorial_system,
+                        update_tutorial_ui.run_if(resource_changed::<TutorialStep>),
+                    ).chain().run_if(tutorial_active.and(not(in_demo))),
+                ),
+            ).chain().run_if(in_state(PauseState::Running)))
+            .add_systems(PostUpdate, (
+                remove_entity_on_collission_system,
+                // Checked whenever the board is clear, not only on a delivery, since the reindeer
+                // can take the last present while Santa's hands are empty
+                // Losing the last life takes precedence over reaching the exit in the same frame
+                (
+                    (
+                        start_escape_system,
+                        next_level_system,
+                    ).chain().run_if(
+                        board_cleared
+                            .and(resource_equals(GameMode::Classic))
+                            .and(resource_equals(WinCondition::ClearAll))
+                    ),
+                    win_system.run_if(on_collision(CollisionKind::ExitPoint).or(resource_changed::<Score>)),
+                    loose_system.run_if(resource_changed::<Lives>),
+                    round_over_system.run_if(resource_equals(GameMode::TimeAttack)),
+                ).chain(),
+            ).chain().run_if(in_state(PauseState::Running)))
+            .add_systems(OnEnter(GameState::GameOver { won: true }), (
+                grade_run_system.run_if(resource_equals(GameMode::Classic).and(not(in_demo))),
+                record_best_score_system.run_if(not(in_demo)),
+                initialize_game_over_ui,
+            ).chain())
+            .add_systems(OnEnter(GameState::GameOver { won: false }), (
+                record_best_score_system.run_if(not(in_demo)),
+                initialize_game_over_ui,
+            ).chain())
+            .add_systems(Update, flash_text_system.run_if(in_game_over))
+            .add_systems(Update, game_over_input_system.run_if(in_game_over.and(not(in_demo))))
+            .add_systems(Update, new_game_plus_input_system.run_if(
+                in_state(GameState::GameOver { won: true }).and(resource_equals(GameMode::Classic)).and(not(in_demo))
+            ))
+            .add_systems(Update, restart_game_system.run_if(in_state(GameState::Playing).or(in_game_over).and(not(in_demo))));
+    }
 }
 
-#[derive(Component, Default)]
-struct Present;
-impl HasSpritePath for Present {
-    fn sprite_path() -> &'static str { "present.png" }
+// The game flows Menu -> Countdown -> Playing -> GameOver. Gameplay systems only run while `Playing`.
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+enum GameState {
+    #[default]
+    Menu,
+    // Reached from the menu to rebind the movement keys
+    Settings,
+    // Every run, including restarts, is spawned here and gives the player a moment before it starts
+    Countdown,
+    Playing,
+    GameOver { won: bool },
 }
-#[derive(Component, Default)]
-struct Snowflake;
-impl HasSpritePath for Snowflake {
-    fn sprite_path() -> &'static str { "snowflake.png" }
+
+fn in_game_over(state: Res<State<GameState>>) -> bool {
+    matches!(state.get(), GameState::GameOver { .. })
 }
 
-#[derive(Component)]
-struct AutoMoving(Vec2);
-#[derive(Component)]
-struct ColliderCircle(f32);
+// Pausing only makes sense while playing, so it lives as a sub-state of `GameState::Playing`
+#[derive(SubStates, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[source(GameState = GameState::Playing)]
+enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
 
+fn gamepad_just_pressed(gamepads: &Query<&Gamepad>, button: GamepadButton) -> bool {
+    gamepads.iter().any(|gamepad| gamepad.just_pressed(button))
+}
 
-fn initialize_automovers<T: Component + Default + HasSpritePath, const N: usize>(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    windows: Query<&Window, With<PrimaryWindow>>,
+// `PauseState` only exists while playing, so outside of it there is nothing to toggle
+fn toggle_pause_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    input_bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    gamepads: Query<&Gamepad>,
+    tutorial_step: Res<TutorialStep>,
+    pause_state: Option<Res<State<PauseState>>>,
+    mut next_state: ResMut<NextState<PauseState>>,
 ) {
-    let primary_window = windows.get_single().unwrap();
-    let width = primary_window.width();
-    let height = primary_window.height();
-    for _ in 0..N {
-        // Select a random position that do not fall within the FREE_ZONE in the center
-        let (x, y) = loop {
-            let x = 32.0 + fastrand::u32(0..width as u32 - 32) as f32;
-            let y = 32.0 + fastrand::u32(0..height as u32 - 32) as f32;
-            let distance_to_center = ((x - width / 2.0).powf(2.0) + (y - height / 2.0).powf(2.0)).sqrt();
-            if distance_to_center > FREE_ZONE {
-                break (x, y);
-            }
-        };
-        // Select random direction
-        let direction = Vec2::new(fastrand::f32(), fastrand::f32()).normalize();
-            
-        commands.spawn((
-            T::default(),
-            Transform::from_xyz(x, y, 0.0),
-            Sprite::from_image(asset_server.load(T::sprite_path())),
-            AutoMoving(direction),
-            ColliderCircle(16.),
-        ));
+    let Some(pause_state) = pause_state else {
+        return;
+    };
+    if rebinding.0.is_some() {
+        return;
+    }
+    // While the tutorial is up Escape skips it instead
+    let pause_keys = input_bindings
+        .keys(InputAction::Pause)
+        .iter()
+        .copied()
+        .filter(|key| *tutorial_step == TutorialStep::Done || *key != KeyCode::Escape);
+    if keys.any_just_pressed(pause_keys) || gamepad_just_pressed(&gamepads, GamepadButton::South) {
+        next_state.set(match pause_state.get() {
+            PauseState::Running => PauseState::Paused,
+            PauseState::Paused => PauseState::Running,
+        });
     }
 }
 
-// `Speed` is a resource becauese all the automvers, and even the santa, share the same speed.
-#[derive(Resource)]
-struct Speed(f32);
-
-fn automoving_system(
-    time: Res<Time>,
-    speed: Res<Speed>,
-    mut automovers: Query<(&mut Transform, &AutoMoving)>,
+// Alt-tabbing away pauses the game. Coming back does not resume it, the player unpauses once ready.
+fn pause_on_focus_loss_system(
+    mut focus_events: EventReader<WindowFocused>,
+    mut next_state: ResMut<NextState<PauseState>>,
 ) {
-    for (mut transform, automover) in automovers.iter_mut() {
-        let direction = automover.0;
-        transform.translation.x += direction.x * speed.0 * time.delta_secs();
-        transform.translation.y += direction.y * speed.0 * time.delta_secs();
+    if focus_events.read().any(|event| !event.focused) {
+        next_state.set(PauseState::Paused);
     }
 }
 
-// Bounce automovers off the screen
-fn bounce_automovers_system(
-    mut automovers: Query<(&mut AutoMoving, &Transform)>,
+// The pause state does not exist during the countdown, so a focus lost there is caught once the run starts
+fn pause_if_unfocused_system(
     windows: Query<&Window, With<PrimaryWindow>>,
+    mut next_state: ResMut<NextState<PauseState>>,
 ) {
-    let primary_window = windows.get_single().unwrap();
-    let width = primary_window.width();
-    let height = primary_window.height();
-    for (mut automover, transform) in automovers.iter_mut() {
-        let x = transform.translation.x;
-        let y = transform.translation.y;
-        let half_size = 32. / 2.;
-
-        // Bounce off left or right edge
-        if x - half_size <= 0.0 || x + half_size >= width {
-            automover.0.x = -automover.0.x; // Reverse x velocity
-        }
-
-        // Bounce off top or bottom edge
-        if y - half_size <= 0.0 || y + half_size >= height {
-            automover.0.y = -automover.0.y; // Reverse y velocity
-        }
+    if windows.get_single().is_ok_and(|window| !window.focused) {
+        next_state.set(PauseState::Paused);
     }
 }
 
+// Walks a new player through the first run. Once done or skipped it stays done for the rest of the session.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Default)]
+enum TutorialStep {
+    #[default]
+    Move,
+    Collect,
+    Avoid(Timer),
+    Done,
+}
 
-#[derive(Component, Default)]
-struct Santa;
-impl HasSpritePath for Santa {
-    fn sprite_path() -> &'static str { "santa.png" }
+const TUTORIAL_AVOID_SECONDS: f32 = 3.0;
+
+#[derive(Component)]
+struct UiTutorialText;
+
+fn tutorial_active(tutorial_step: Res<TutorialStep>) -> bool {
+    *tutorial_step != TutorialStep::Done
 }
 
-fn initialize_santa(
+fn tutorial_text(tutorial_step: &TutorialStep, input_bindings: &InputBindings) -> String {
+    match tutorial_step {
+        TutorialStep::Move => format!("Move with {}", input_bindings.layouts_hint()),
+        TutorialStep::Collect => "Collect a present".to_string(),
+        TutorialStep::Avoid(_) => "Avoid snowflakes!".to_string(),
+        TutorialStep::Done => String::new(),
+    }
+}
+
+fn initialize_tutorial_ui(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    windows: Query<&Window, With<PrimaryWindow>>,
+    tutorial_step: Res<TutorialStep>,
+    input_bindings: Res<InputBindings>,
 ) {
-    let primary_window = windows.get_single().unwrap();
     commands.spawn((
-        Santa::default(),
-        // Santa spawns in the middle of the screen
-        Transform::from_xyz(primary_window.width() / 2.0, primary_window.height() / 2.0, 0.0),
-        Sprite::from_image(asset_server.load(Santa::sprite_path())),
-        ColliderCircle(16.),
-    ));
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            bottom: Val::Px(60.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        RunEntity,
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new(tutorial_text(&tutorial_step, &input_bindings)),
+            TextFont { font_size: 32.0, ..default() },
+            UiTutorialText,
+        ));
+    });
 }
 
-fn move_santa_system(
-    keys: Res<ButtonInput<KeyCode>>,
+fn advance_tutorial_system(
     time: Res<Time>,
-    speed: Res<Speed>,
-    windows: Query<&Window, With<PrimaryWindow>>,
-    mut santa: Query<&mut Transform, With<Santa>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    game_mode: Res<GameMode>,
+    mut tutorial_step: ResMut<TutorialStep>,
+    mut collision_events: EventReader<CollisionEvent>,
+    santa: Query<&Velocity, With<Santa>>,
 ) {
-    let primary_window = windows.get_single().unwrap();
-    let width = primary_window.width();
-    let height = primary_window.height();
-
-    let mut santa_transform = santa.single_mut();
-
-    if keys.pressed(KeyCode::ArrowLeft) || keys.pressed(KeyCode::KeyJ) {
-        if santa_transform.translation.x > 32. / 2. {
-            santa_transform.translation.x -= speed.0 * time.delta_secs();
-        }
-    }
-    if keys.pressed(KeyCode::ArrowRight) || keys.pressed(KeyCode::KeyL) {
-        if santa_transform.translation.x < width - 32. / 2. {
-            santa_transform.translation.x += speed.0 * time.delta_secs();
-        }
+    let collected = collision_events.read().any(|event| event.kind.is_present());
+    // Ticking is not a step change, so it must not trigger a UI update
+    if let TutorialStep::Avoid(timer) = tutorial_step.bypass_change_detection() {
+        timer.tick(time.delta());
     }
-    if keys.pressed(KeyCode::ArrowUp) || keys.pressed(KeyCode::KeyI) {
-        if santa_transform.translation.y < height - 32. / 2. {
-            santa_transform.translation.y += speed.0 * time.delta_secs();
+    let next_step = match tutorial_step.as_ref() {
+        _ if keys.just_pressed(KeyCode::Escape) => Some(TutorialStep::Done),
+        TutorialStep::Move if santa.single().0 != Vec2::ZERO => Some(if *game_mode == GameMode::Survival {
+            // There are no presents to collect in survival
+            TutorialStep::Avoid(Timer::from_seconds(TUTORIAL_AVOID_SECONDS, TimerMode::Once))
+        } else {
+            TutorialStep::Collect
+        }),
+        TutorialStep::Collect if collected => {
+            Some(TutorialStep::Avoid(Timer::from_seconds(TUTORIAL_AVOID_SECONDS, TimerMode::Once)))
         }
+        TutorialStep::Avoid(timer) if timer.finished() => Some(TutorialStep::Done),
+        _ => None,
+    };
+    if let Some(next_step) = next_step {
+        *tutorial_step = next_step;
     }
-    if keys.pressed(KeyCode::ArrowDown) || keys.pressed(KeyCode::KeyK) {
-        if santa_transform.translation.y > 32. / 2. {
-            santa_transform.translation.y -= speed.0 * time.delta_secs();
-        }
+}
+
+fn update_tutorial_ui(
+    tutorial_step: Res<TutorialStep>,
+    input_bindings: Res<InputBindings>,
+    mut query: Query<&mut Text, With<UiTutorialText>>,
+) {
+    for mut text in query.iter_mut() {
+        text.0 = tutorial_text(&tutorial_step, &input_bindings);
     }
 }
 
-// Trait for generic systems where we only need to know the entity(in this case collision events)
-trait WithEntity {
-    fn new(entity: Entity) -> Self;
-    fn entity(&self) -> Entity;
+fn initialize_pause_ui(
+    mut commands: Commands,
+    input_bindings: Res<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    rebinding.0 = None;
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        StateScoped(PauseState::Paused),
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new("Paused"),
+            TextFont { font_size: 48.0, ..default() },
+        ));
+        for (number, action) in (1..).zip(InputAction::ALL) {
+            parent.spawn(Node { column_gap: Val::Px(10.0), ..default() }).with_children(|parent| {
+                parent.spawn(Text::new(format!("{}.", number)));
+                parent.spawn((Text::new(binding_label(action, &input_bindings, &rebinding)), UiBindingLabel(action)));
+            });
+        }
+        parent.spawn(Text::new("Press a number to rebind its action"));
+    });
 }
 
-#[derive(Event)]
-pub struct CollisionWithPresentEvent(Entity);
-impl WithEntity for CollisionWithPresentEvent {
-    fn new(entity: Entity) -> Self {
-        Self(entity)
-    }
-    fn entity(&self) -> Entity {
-        self.0
+// Marks entities that belong to a single run, so they can be cleaned up before the next one starts
+#[derive(Component, Default)]
+struct RunEntity;
+
+fn cleanup_run_system(
+    mut commands: Commands,
+    mut collision_events: ResMut<Events<CollisionEvent>>,
+    run_entities: Query<Entity, With<RunEntity>>,
+) {
+    for entity in run_entities.iter() {
+        commands.entity(entity).despawn_recursive();
     }
+    // Stale collisions from the previous run must not hit the new Santa
+    collision_events.clear();
 }
-#[derive(Event)]
-pub struct CollisionWithSnowflakeEvent(Entity);
-impl WithEntity for CollisionWithSnowflakeEvent {
-    fn new(entity: Entity) -> Self {
-        Self(entity)
-    }
-    fn entity(&self) -> Entity {
-        self.0
+
+#[allow(clippy::too_many_arguments)]
+fn reset_run_system(
+    difficulty: Res<Difficulty>,
+    mut speed: ResMut<Speed>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut level: ResMut<Level>,
+    mut high_score_beaten: ResMut<HighScoreBeaten>,
+    mut game_timer: ResMut<GameTimer>,
+    mut countdown: ResMut<Countdown>,
+    mut round_timer: ResMut<RoundTimer>,
+    game_mode: Res<GameMode>,
+    mut snowflake_spawner: ResMut<SnowflakeSpawner>,
+    mut max_snowflakes: ResMut<MaxSnowflakes>,
+    mut run_stats: ResMut<RunStats>,
+    mut run_grade: ResMut<RunGrade>,
+    mut combo: ResMut<Combo>,
+    starting_lives: Res<StartingLives>,
+) {
+    *run_stats = RunStats::default();
+    *combo = Combo::default();
+    run_grade.0 = None;
+    speed.0 = difficulty.speed();
+    score.0 = 0;
+    lives.0 = starting_lives.0;
+    level.0 = 1;
+    high_score_beaten.0 = false;
+    game_timer.0.reset();
+    countdown.0.reset();
+    round_timer.0.reset();
+    if *game_mode == GameMode::Survival {
+        *snowflake_spawner = SnowflakeSpawner::new(SURVIVAL_SPAWN_INTERVAL);
+        max_snowflakes.0 = SURVIVAL_MAX_SNOWFLAKES;
+    } else {
+        *snowflake_spawner = SnowflakeSpawner::new(difficulty.spawn_interval());
+        max_snowflakes.0 = Snowflake::spawn_count(*difficulty, 1);
     }
 }
 
-fn detect_collisions_system<C: Component, E: Event + WithEntity>(
-    mut event_writer: EventWriter<E>,
-    objects: Query<(Entity, &Transform, &ColliderCircle), With<C>>,
-    santa: Query<(&Transform, &ColliderCircle), With<Santa>>,
+// Measures how long the current run has been played, excluding pauses
+#[derive(Resource)]
+struct GameTimer(Stopwatch);
+
+fn tick_timer_system(
+    time: Res<Time>,
+    mut game_timer: ResMut<GameTimer>,
 ) {
-    let (santa_transform, santa_collider) = santa.single();
-    for (entity, object_transform, object_collider) in objects.iter() {
-        let object_position = object_transform.translation;
-        let object_radius = object_collider.0;
+    game_timer.0.tick(time.delta());
+}
+
+// Summary of the current run, shown on the game over screen
+#[derive(Resource, Default)]
+struct RunStats {
+    presents_collected: u32,
+    hits_taken: u32,
+    time_survived: f32,
+    peak_speed: f32,
+    distance_travelled: f32,
+}
 
-        if object_position.distance(santa_transform.translation) < (santa_collider.0 + object_radius) * 1.7 {
-            event_writer.send(E::new(entity));
+fn track_run_stats_system(
+    time: Res<Time>,
+    speed: Res<Speed>,
+    mut run_stats: ResMut<RunStats>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    for event in collision_events.read() {
+        match event.kind {
+            CollisionKind::Present | CollisionKind::GoldPresent => run_stats.presents_collected += 1,
+            CollisionKind::Snowflake | CollisionKind::Snowman | CollisionKind::SleighHazard => run_stats.hits_taken += 1,
+            CollisionKind::Sleigh | CollisionKind::ExitPoint | CollisionKind::HeartPickup | CollisionKind::ShieldPickup
+            | CollisionKind::SlowMoPickup | CollisionKind::FreezePickup | CollisionKind::MagnetPickup
+            | CollisionKind::BoostPickup | CollisionKind::BombPickup | CollisionKind::CandyCane
+            | CollisionKind::ReindeerPickup => {}
         }
     }
+    run_stats.time_survived += time.delta_secs();
+    run_stats.peak_speed = run_stats.peak_speed.max(speed.0);
 }
 
-fn remove_entity_on_collission_system<E: Event + WithEntity>(
-    mut commands: Commands,
-    mut event_reader: EventReader<E>,
+// Formats seconds as MM:SS
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn restart_game_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    input_bindings: Res<InputBindings>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    mut best_score: ResMut<BestScore>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    for event in event_reader.read() {
-        commands.entity(event.entity()).despawn();
+    if input_bindings.just_pressed(InputAction::Restart, &keys) {
+        // An abandoned run still counts, a finished one was already recorded on game over
+        if *state.get() == GameState::Playing {
+            update_best_score(&mut best_score, score.0);
+        }
+        next_state.set(GameState::Countdown);
     }
 }
 
-#[derive(Resource)]
-struct Score(u32);
-#[derive(Resource)]
-struct Lives(u32);
+const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::srgb(0.3, 0.3, 0.3);
 
 #[derive(Component)]
-struct UiScoreText;
+enum MenuButton {
+    Play(Difficulty),
+    Settings,
+    Quit,
+}
+
+const COUNTDOWN_SECONDS: f32 = 3.0;
+
+#[derive(Resource)]
+struct Countdown(Timer);
+
+// The big text in the middle of the screen. Its timer keeps "Go!" visible for a moment once the run starts.
 #[derive(Component)]
-struct UiHeart(u32);
+struct CountdownText(Timer);
 
-fn initialize_ui(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    lives: Res<Lives>,
-) {
-    // Add score label
+fn initialize_countdown_ui(mut commands: Commands) {
     commands.spawn((
         Node {
-            position_type: PositionType::Absolute,
-            left: Val::Px(10.0),
-            top: Val::Px(10.0),
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
             ..default()
         },
-        Text::new("Score: 0"),
-        UiScoreText,
-    ));
-    // Create Hearts
+        RunEntity,
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new(format!("{}", COUNTDOWN_SECONDS as u32)),
+            TextFont { font_size: 96.0, ..default() },
+            CountdownText(Timer::from_seconds(0.5, TimerMode::Once)),
+        ));
+    });
+}
+
+fn countdown_system(
+    time: Res<Time>,
+    mut countdown: ResMut<Countdown>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut text: Query<&mut Text, With<CountdownText>>,
+) {
+    countdown.0.tick(time.delta());
+    let mut text = text.single_mut();
+    if countdown.0.finished() {
+        text.0 = "Go!".to_string();
+        next_state.set(GameState::Playing);
+    } else {
+        text.0 = format!("{}", countdown.0.remaining_secs().ceil() as u32);
+    }
+}
+
+fn hide_countdown_text_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut text: Query<(&Parent, &mut CountdownText)>,
+) {
+    for (parent, mut countdown_text) in text.iter_mut() {
+        if countdown_text.0.tick(time.delta()).just_finished() {
+            commands.entity(parent.get()).despawn_recursive();
+        }
+    }
+}
+
+fn initialize_menu(
+    mut commands: Commands,
+    difficulty: Res<Difficulty>,
+    game_mode: Res<GameMode>,
+    best_grade: Res<BestGrade>,
+) {
     commands.spawn((
         Node {
-            position_type: PositionType::Absolute,
-            right: Val::Px(10.0),
-            top: Val::Px(10.0),
-            column_gap: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(10.0),
             ..default()
         },
+        StateScoped(GameState::Menu),
     )).with_children(|parent| {
-        for i in 1..=lives.0 {
-            parent.spawn((
-                Node {
-                    width: Val::Px(21.0),
-                    height: Val::Px(18.0),
-                    ..default()
-                },
-                ImageNode::new(asset_server.load("heart.png")),
-                UiHeart(i),
-            ));
+        parent.spawn((
+            Text::new("Bevy Santa Demo"),
+            TextFont { font_size: 48.0, ..default() },
+        ));
+        // Picking a difficulty starts the game right away
+        for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard] {
+            spawn_button(parent, MenuButton::Play(difficulty), Text::new(difficulty.name()));
         }
+        for (button, label) in [(MenuButton::Settings, "Settings"), (MenuButton::Quit, "Quit")] {
+            spawn_button(parent, button, Text::new(label));
+        }
+        parent.spawn((Text::new(difficulty_menu_label(*difficulty)), UiDifficultyText));
+        parent.spawn((Text::new(game_mode_menu_label(*game_mode)), UiGameModeText));
+        parent.spawn(Text::new(format!("Best rank: {}", best_grade.0.map_or("-", Grade::letter))));
     });
 }
 
-fn score_points_system(
-    mut score: ResMut<Score>,
-    mut event_reader: EventReader<CollisionWithPresentEvent>,
+fn spawn_button(parent: &mut ChildBuilder, button: impl Bundle, label: impl Bundle) {
+    parent.spawn((
+        Button,
+        Node {
+            width: Val::Px(200.0),
+            height: Val::Px(50.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        button,
+    )).with_children(|parent| {
+        parent.spawn(label);
+    });
+}
+
+fn menu_button_system(
+    interactions: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut difficulty: ResMut<Difficulty>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
 ) {
-    for _ in event_reader.read() {
-        score.0 += 1;
+    for (interaction, button) in interactions.iter() {
+        if *interaction == Interaction::Pressed {
+            match button {
+                MenuButton::Play(selected) => {
+                    *difficulty = *selected;
+                    next_state.set(GameState::Countdown);
+                }
+                MenuButton::Settings => next_state.set(GameState::Settings),
+                MenuButton::Quit => { exit.send(AppExit::Success); }
+            }
+        }
     }
 }
 
-fn update_score_ui(
-    score: Res<Score>,
-    mut query: Query<&mut Text, With<UiScoreText>>,
-) {
-    let mut text = query.single_mut();
-    text.0 = format!("Score: {}", score.0);
+// Everything the player can do with a key, independent of the keyboard layout
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Pause,
+    Restart,
+    Dash,
+    Sprint,
 }
 
-fn take_lives_system(
-    mut lives: ResMut<Lives>,
-    mut event_reader: EventReader<CollisionWithSnowflakeEvent>,
-) {
-    for _ in event_reader.read() {
-        lives.0 -= 1;
+impl InputAction {
+    const ALL: [InputAction; 8] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Pause,
+        InputAction::Restart,
+        InputAction::Dash,
+        InputAction::Sprint,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            InputAction::MoveUp => "Move up",
+            InputAction::MoveDown => "Move down",
+            InputAction::MoveLeft => "Move left",
+            InputAction::MoveRight => "Move right",
+            InputAction::Pause => "Pause",
+            InputAction::Restart => "Restart",
+            InputAction::Dash => "Dash",
+            InputAction::Sprint => "Sprint",
+        }
+    }
+    fn direction(self) -> Vec2 {
+        match self {
+            InputAction::MoveUp => Vec2::Y,
+            InputAction::MoveDown => Vec2::NEG_Y,
+            InputAction::MoveLeft => Vec2::NEG_X,
+            InputAction::MoveRight => Vec2::X,
+            InputAction::Pause | InputAction::Restart | InputAction::Dash | InputAction::Sprint => Vec2::ZERO,
+        }
     }
 }
 
-fn update_lives_ui(
-    mut commands: Commands,
-    lives: Res<Lives>,
-    mut query: Query<(Entity, &UiHeart), With<UiHeart>>,
-) {
-    for (entity, ui_heart) in query.iter_mut() {
-        if ui_heart.0 > lives.0 {
-            commands.entity(entity).despawn();
+// Every key that triggers an action. Adding a keyboard layout is a matter of adding its keys here.
+#[derive(Resource, Serialize, Deserialize)]
+struct InputBindings(HashMap<InputAction, Vec<KeyCode>>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (InputAction::MoveUp, vec![KeyCode::ArrowUp, KeyCode::KeyW, KeyCode::KeyI]),
+            (InputAction::MoveDown, vec![KeyCode::ArrowDown, KeyCode::KeyS, KeyCode::KeyK]),
+            (InputAction::MoveLeft, vec![KeyCode::ArrowLeft, KeyCode::KeyA, KeyCode::KeyJ]),
+            (InputAction::MoveRight, vec![KeyCode::ArrowRight, KeyCode::KeyD, KeyCode::KeyL]),
+            (InputAction::Pause, vec![KeyCode::Escape, KeyCode::KeyP]),
+            (InputAction::Dash, vec![KeyCode::Space, KeyCode::ShiftLeft]),
+            // Left Shift dashes, so sprinting moved over to Left Control and kept Right Shift
+            (InputAction::Sprint, vec![KeyCode::ControlLeft, KeyCode::ShiftRight]),
+            (InputAction::Restart, vec![KeyCode::KeyR]),
+        ]))
+    }
+}
+
+impl InputBindings {
+    fn keys(&self, action: InputAction) -> &[KeyCode] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    fn pressed(&self, action: InputAction, keys: &ButtonInput<KeyCode>) -> bool {
+        keys.any_pressed(self.keys(action).iter().copied())
+    }
+
+    fn just_pressed(&self, action: InputAction, keys: &ButtonInput<KeyCode>) -> bool {
+        keys.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    // Makes `key` the only key of the action. A key already used by another action is taken away from it.
+    fn bind(&mut self, action: InputAction, key: KeyCode) {
+        for keys in self.0.values_mut() {
+            keys.retain(|bound| *bound != key);
         }
+        self.0.insert(action, vec![key]);
+    }
+
+    // Names the layouts in up/left/down/right order, e.g. "arrows/WASD/IJKL" for the defaults
+    fn layouts_hint(&self) -> String {
+        let actions = [InputAction::MoveUp, InputAction::MoveLeft, InputAction::MoveDown, InputAction::MoveRight];
+        let arrows = [KeyCode::ArrowUp, KeyCode::ArrowLeft, KeyCode::ArrowDown, KeyCode::ArrowRight];
+        (0..)
+            .map_while(|i| actions.iter().map(|action| self.keys(*action).get(i).copied()).collect::<Option<Vec<_>>>())
+            .map(|layout| if layout == arrows {
+                "arrows".to_string()
+            } else {
+                layout.into_iter().map(key_name).collect()
+            })
+            .collect::<Vec<_>>()
+            .join("/")
     }
 }
 
-fn speed_up_on_score(
-    mut speed: ResMut<Speed>
-    , mut event_reader: EventReader<CollisionWithPresentEvent>
-) {
-    for _ in event_reader.read() {
-        speed.0 += 50.0;
+impl std::fmt::Display for InputBindings {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for action in InputAction::ALL {
+            let keys: Vec<String> = self.keys(action).iter().copied().map(key_name).collect();
+            writeln!(f, "{}: {}", action.name(), keys.join(", "))?;
+        }
+        Ok(())
     }
 }
 
-fn win_system(
-    mut exit: EventWriter<AppExit>,
-    query: Query<(), With<Present>>,
-) {
-    if query.is_empty() {
-        println!("You win!");
-        exit.send(AppExit::Success);
+// The action waiting for its new key, if any
+#[derive(Resource, Default)]
+struct Rebinding(Option<InputAction>);
+
+#[derive(Component)]
+struct UiBindingLabel(InputAction);
+#[derive(Component)]
+struct SettingsBackButton;
+
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")).unwrap_or(&name).to_string()
+}
+
+fn binding_label(action: InputAction, input_bindings: &InputBindings, rebinding: &Rebinding) -> String {
+    if rebinding.0 == Some(action) {
+        format!("{}: press a key", action.name())
+    } else {
+        let keys: Vec<String> = input_bindings.keys(action).iter().copied().map(key_name).collect();
+        format!("{}: {}", action.name(), keys.join("/"))
     }
 }
 
-fn loose_system(
-    mut exit: EventWriter<AppExit>,
-    lives: Res<Lives>,
+// Writes the next pressed key into the action being rebound, Escape cancels.
+// The key is consumed, so it does not also trigger whatever it is bound to in the same frame.
+fn capture_binding(
+    keys: &mut ButtonInput<KeyCode>,
+    input_bindings: &mut InputBindings,
+    rebinding: &mut Rebinding,
 ) {
-    if lives.0 == 0 {
-        println!("You loose!");
-        exit.send(AppExit::Success);
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+    let Some(key) = keys.get_just_pressed().next().copied() else {
+        return;
+    };
+    keys.clear_just_pressed(key);
+    rebinding.0 = None;
+    if key != KeyCode::Escape {
+        input_bindings.bind(action, key);
     }
-}
\ No newline at end of file
+}
+
+fn initialize_settings(
+    mut commands: Commands,
+    input_bindings: Res<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    rebinding.0 = None;
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        StateScoped(GameState::Settings),
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new("Settings"),
+            TextFont { font_size: 48.0, ..default() },
+        ));
+        for action in InputAction::ALL {
+            let label = binding_label(action, &input_bindings, &rebinding);
+            spawn_button(parent, action, (Text::new(label), UiBindingLabel(action)));
+        }
+        spawn_button(parent, SettingsBackButton, Text::new("Back"));
+        parent.spawn(Text::new("Click a key binding to change it / Esc to go back"));
+    });
+}
+
+fn settings_button_system(
+    actions: Query<(&Interaction, &InputAction), Changed<Interaction>>,
+    back: Query<&Interaction, (Changed<Interaction>, With<SettingsBackButton>)>,
+    mut rebinding: ResMut<Rebinding>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, action) in actions.iter() {
+        if *interaction == Interaction::Pressed {
+            rebinding.0 = Some(*action);
+        }
+    }
+    if back.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+// Captures the next key for the action being edited, Escape leaves the settings when nothing is edited
+fn rebind_system(
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut input_bindings: ResMut<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if rebinding.0.is_some() {
+        capture_binding(&mut keys, &mut input_bindings, &mut rebinding);
+    } else if keys.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+// On the pause screen a digit picks the action to rebind, then the next key is captured for it
+fn pause_rebind_system(
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut input_bindings: ResMut<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    if rebinding.0.is_some() {
+        capture_binding(&mut keys, &mut input_bindings, &mut rebinding);
+        return;
+    }
+    let digits = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8];
+    for (digit, action) in digits.into_iter().zip(InputAction::ALL) {
+        if keys.just_pressed(digit) {
+            keys.clear_just_pressed(digit);
+            rebinding.0 = Some(action);
+        }
+    }
+}
+
+fn update_binding_labels(
+    input_bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    mut labels: Query<(&mut Text, &UiBindingLabel)>,
+) {
+    for (mut text, label) in labels.iter_mut() {
+        text.0 = binding_label(label.0, &input_bindings, &rebinding);
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+    fn lives(self) -> u32 {
+        match self {
+            Difficulty::Easy => 5,
+            Difficulty::Normal => 3,
+            Difficulty::Hard => 2,
+        }
+    }
+    // Presents to deliver on the first level, more means a longer stay among the snowflakes
+    fn presents(self) -> usize {
+        match self {
+            Difficulty::Easy => 8,
+            Difficulty::Normal => 10,
+            Difficulty::Hard => 12,
+        }
+    }
+    fn snowflakes(self) -> usize {
+        match self {
+            Difficulty::Easy => 6,
+            Difficulty::Normal => 10,
+            Difficulty::Hard => 14,
+        }
+    }
+    // Starting speed of the automovers
+    fn speed(self) -> f32 {
+        match self {
+            Difficulty::Easy => 80.0,
+            Difficulty::Normal => 100.0,
+            Difficulty::Hard => 120.0,
+        }
+    }
+    // How much the automovers speed up with every collected present
+    fn speed_increment(self) -> f32 {
+        match self {
+            Difficulty::Easy => 30.0,
+            Difficulty::Normal => 50.0,
+            Difficulty::Hard => 75.0,
+        }
+    }
+    // Seconds between snowflakes spawning until the level's count is reached
+    fn spawn_interval(self) -> f32 {
+        match self {
+            Difficulty::Easy => 2.0,
+            Difficulty::Normal => 1.5,
+            Difficulty::Hard => 1.0,
+        }
+    }
+    // Radius of the safe area around the center where nothing spawns, harder means less room
+    fn free_zone(self) -> f32 {
+        match self {
+            Difficulty::Easy => 240.0,
+            Difficulty::Normal => 200.0,
+            Difficulty::Hard => 160.0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct UiDifficultyText;
+
+fn difficulty_menu_label(difficulty: Difficulty) -> String {
+    format!("Difficulty: {} (press 1, 2 or 3 to change)", difficulty.name())
+}
+
+fn select_difficulty_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut difficulty: ResMut<Difficulty>,
+    mut text: Query<&mut Text, With<UiDifficultyText>>,
+) {
+    let selected = if keys.just_pressed(KeyCode::Digit1) {
+        Difficulty::Easy
+    } else if keys.just_pressed(KeyCode::Digit2) {
+        Difficulty::Normal
+    } else if keys.just_pressed(KeyCode::Digit3) {
+        Difficulty::Hard
+    } else {
+        return;
+    };
+    *difficulty = selected;
+    for mut text in text.iter_mut() {
+        text.0 = difficulty_menu_label(selected);
+    }
+}
+
+// Classic ends once all presents are collected, Endless replaces every collected present with a new one,
+// TimeAttack is about collecting as many presents as possible before the clock runs out
+// and Survival has no presents at all, only more and more snowflakes to dodge
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum GameMode {
+    #[default]
+    Classic,
+    Endless,
+    TimeAttack,
+    Survival,
+}
+
+impl GameMode {
+    fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Endless => "Endless",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Survival => "Survival",
+        }
+    }
+    fn next(self) -> Self {
+        match self {
+            GameMode::Classic => GameMode::Endless,
+            GameMode::Endless => GameMode::TimeAttack,
+            GameMode::TimeAttack => GameMode::Survival,
+            GameMode::Survival => GameMode::Classic,
+        }
+    }
+    // Presents taken off the board come back somewhere else, also whenever the board can never be cleared
+    fn respawns_presents(self, win_condition: WinCondition) -> bool {
+        matches!(self, GameMode::Endless | GameMode::TimeAttack) || matches!(win_condition, WinCondition::ReachScore(_))
+    }
+}
+
+fn respawns_presents(game_mode: Res<GameMode>, win_condition: Res<WinCondition>) -> bool {
+    game_mode.respawns_presents(*win_condition)
+}
+
+#[derive(Component)]
+struct UiGameModeText;
+
+fn game_mode_menu_label(game_mode: GameMode) -> String {
+    format!("Mode: {} (press Tab to change)", game_mode.name())
+}
+
+fn select_game_mode_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut game_mode: ResMut<GameMode>,
+    mut text: Query<&mut Text, With<UiGameModeText>>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        *game_mode = game_mode.next();
+        for mut text in text.iter_mut() {
+            text.0 = game_mode_menu_label(*game_mode);
+        }
+    }
+}
+
+// Idling on the menu starts a demo run where Santa plays by himself
+const MENU_IDLE_SECONDS: f32 = 10.0;
+// Snowflakes closer than this push the demo Santa away
+const DEMO_AVOID_DISTANCE: f32 = 100.0;
+
+#[derive(Resource)]
+struct DemoMode(bool);
+#[derive(Resource)]
+struct MenuIdleTimer(Timer);
+
+fn in_demo(demo_mode: Res<DemoMode>) -> bool {
+    demo_mode.0
+}
+
+fn reset_menu_idle_timer_system(mut idle_timer: ResMut<MenuIdleTimer>) {
+    idle_timer.0.reset();
+}
+
+fn start_demo_system(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut idle_timer: ResMut<MenuIdleTimer>,
+    mut demo_mode: ResMut<DemoMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let any_input = keys.get_pressed().next().is_some()
+        || mouse.get_pressed().next().is_some()
+        || cursor_moved.read().next().is_some();
+    if any_input {
+        idle_timer.0.reset();
+        return;
+    }
+    if idle_timer.0.tick(time.delta()).just_finished() {
+        demo_mode.0 = true;
+        next_state.set(GameState::Countdown);
+    }
+}
+
+fn cancel_demo_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut demo_mode: ResMut<DemoMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.get_just_pressed().next().is_some() || mouse.get_just_pressed().next().is_some() {
+        demo_mode.0 = false;
+        next_state.set(GameState::Menu);
+    }
+}
+
+// A finished demo run goes straight back to the menu
+fn end_demo_system(
+    mut demo_mode: ResMut<DemoMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    demo_mode.0 = false;
+    next_state.set(GameState::Menu);
+}
+
+fn initialize_demo_ui(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            bottom: Val::Px(20.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        RunEntity,
+    )).with_children(|parent| {
+        parent.spawn(Text::new("Demo - press any key"));
+    });
+}
+
+// Steers the demo Santa towards the nearest target while avoiding snowflakes
+fn demo_santa_direction(santa: Vec2, targets: impl Iterator<Item = Vec2>, snowflakes: impl Iterator<Item = Vec2>) -> Vec2 {
+    let seek = targets
+        .min_by(|a, b| a.distance_squared(santa).total_cmp(&b.distance_squared(santa)))
+        .map(|present| (present - santa).normalize_or_zero())
+        .unwrap_or(Vec2::ZERO);
+    // The closer the snowflake, the stronger the push away from it
+    let avoid: Vec2 = snowflakes
+        .filter(|snowflake| snowflake.distance(santa) < DEMO_AVOID_DISTANCE)
+        .map(|snowflake| {
+            let away = santa - snowflake;
+            away.normalize_or_zero() * (1.0 - away.length() / DEMO_AVOID_DISTANCE)
+        })
+        .sum();
+    seek + avoid * 2.0
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn demo_santa_system(
+    time: Res<Time>,
+    speed: Res<SantaSpeed>,
+    acceleration: Res<Acceleration>,
+    friction: Res<Friction>,
+    mut santa: Query<(&Transform, &mut Velocity, &mut Facing, &mut Sprite, &Carrying), With<Santa>>,
+    presents: Query<&Transform, (Or<(With<Present>, With<GoldPresent>)>, Without<Santa>)>,
+    sleighs: Query<&Transform, (With<Sleigh>, Without<Santa>)>,
+    exits: Query<&Transform, (With<ExitPoint>, Without<Santa>)>,
+    snowflakes: Query<&Transform, (With<Snowflake>, Without<Santa>)>,
+) {
+    let (santa_transform, mut velocity, mut facing, mut sprite, carrying) = santa.single_mut();
+    // Deliver once the arms are full or there is nothing left to pick up
+    let targets = if !exits.is_empty() {
+        exits.iter().collect::<Vec<_>>()
+    } else if carrying.0 >= MAX_CARRIED_PRESENTS || (carrying.0 > 0 && presents.is_empty()) {
+        sleighs.iter().collect()
+    } else {
+        presents.iter().collect()
+    };
+    let direction = demo_santa_direction(
+        santa_transform.translation.truncate(),
+        targets.into_iter().map(|transform| transform.translation.truncate()),
+        snowflakes.iter().map(|transform| transform.translation.truncate()),
+    );
+    let max_speed = speed.0 * carrying_speed_factor(carrying);
+    face_towards(&mut facing, &mut sprite, direction);
+    // The demo Santa always goes full speed
+    steer_santa(&mut velocity, direction.normalize_or_zero(), max_speed, acceleration.0, friction.0, time.delta_secs());
+}
+
+// Highlight any button under the cursor, so it is clear it can be clicked
+#[allow(clippy::type_complexity)]
+fn button_hover_system(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, mut color) in buttons.iter_mut() {
+        color.0 = match interaction {
+            Interaction::Hovered | Interaction::Pressed => HOVERED_BUTTON,
+            Interaction::None => NORMAL_BUTTON,
+        };
+    }
+}
+
+fn start_game_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::Enter) {
+        next_state.set(GameState::Countdown);
+    }
+}
+
+// The playing field in world units, gameplay reads this instead of the window so it also runs headless
+#[derive(Resource, Clone, Copy)]
+struct GameBounds {
+    width: f32,
+    height: f32,
+}
+
+impl Default for GameBounds {
+    // Matches the default window, until the real one is read
+    fn default() -> Self {
+        Self { width: 1280.0, height: 720.0 }
+    }
+}
+
+impl GameBounds {
+    fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+}
+
+// Without a window, e.g. in tests, the bounds are simply left as they are
+fn sync_bounds_system(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut bounds: ResMut<GameBounds>,
+) {
+    if let Ok(primary_window) = windows.get_single() {
+        bounds.width = primary_window.width();
+        bounds.height = primary_window.height();
+    }
+}
+
+fn setup_camera(
+    mut commands: Commands,
+    bounds: Res<GameBounds>,
+) {
+    let home = camera_home(&bounds);
+    commands.spawn((
+        Camera2d,
+        Transform::from_translation(home),
+        CameraHome(home),
+    ));
+}
+
+fn toggle_fullscreen_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !keys.just_pressed(KeyCode::F11) {
+        return;
+    }
+    let mut primary_window = windows.single_mut();
+    primary_window.mode = match primary_window.mode {
+        WindowMode::Windowed => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+        _ => WindowMode::Windowed,
+    };
+}
+
+// Everything is laid out in window coordinates with the origin in the bottom left corner,
+// so the camera looks at the middle of the bounds. Derived from the size alone, so resizing
+// back and forth never drifts and the FREE_ZONE stays in the center of the screen.
+fn camera_home(bounds: &GameBounds) -> Vec3 {
+    (bounds.size() / 2.0).extend(0.0)
+}
+
+fn recenter_camera_system(
+    bounds: Res<GameBounds>,
+    mut cameras: Query<(&mut Transform, &mut CameraHome)>,
+) {
+    let home = camera_home(&bounds);
+    for (mut transform, mut camera_home) in cameras.iter_mut() {
+        camera_home.0 = home;
+        transform.translation = home;
+    }
+}
+
+// A smaller window may leave things outside of it, pull them back into view
+#[allow(clippy::type_complexity)]
+fn fit_to_window_system(
+    bounds: Res<GameBounds>,
+    mut objects: Query<(&mut Transform, Option<&ColliderCircle>, Has<Sleigh>), Or<(With<Santa>, With<AutoMoving>, With<Sleigh>)>>,
+) {
+    let size = bounds.size();
+    for (mut transform, collider, is_sleigh) in objects.iter_mut() {
+        let half_size = collider.map_or(32. / 2., |collider| collider.0);
+        let position = if is_sleigh {
+            // Stays parked in the bottom right corner
+            Vec2::new(size.x - 48.0, 48.0)
+        } else {
+            transform.translation.truncate().clamp(Vec2::splat(half_size), (size - half_size).max(Vec2::splat(half_size)))
+        };
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+const TOAST_SECONDS: f32 = 2.0;
+
+// Short notice at the bottom of the screen that removes itself
+#[derive(Component)]
+struct Toast(Timer);
+
+fn spawn_toast(commands: &mut Commands, message: &str) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            bottom: Val::Px(10.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Toast(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once)),
+    )).with_child(Text::new(message));
+}
+
+fn toast_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Screenshots taken within the same millisecond still get distinct names
+#[derive(Resource, Default)]
+struct ScreenshotCounter(u32);
+
+fn screenshot_path(counter: u32) -> PathBuf {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let directory = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from))
+        .unwrap_or_default();
+    directory.join(format!("screenshot_{}_{}.png", millis, counter))
+}
+
+fn take_screenshot_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut counter: ResMut<ScreenshotCounter>,
+) {
+    if !keys.just_pressed(KeyCode::F12) {
+        return;
+    }
+    counter.0 += 1;
+    let path = screenshot_path(counter.0);
+    commands
+        .spawn(Screenshot::primary_window())
+        .observe(move |trigger: Trigger<ScreenshotCaptured>, mut commands: Commands| {
+            let saved = match trigger.event().0.clone().try_into_dynamic() {
+                // The alpha channel holds brightness values with HDR, so it is dropped
+                Ok(image) => image.to_rgb8().save(&path).map_err(|error| error.to_string()),
+                Err(error) => Err(error.to_string()),
+            };
+            match saved {
+                Ok(()) => spawn_toast(&mut commands, "Saved screenshot"),
+                Err(error) => error!("Could not save screenshot to {}: {}", path.display(), error),
+            }
+        });
+}
+
+// Where the camera rests; shake offsets are always applied relative to it
+#[derive(Component)]
+struct CameraHome(Vec3);
+
+const SCREEN_SHAKE_SECONDS: f32 = 0.3;
+const SCREEN_SHAKE_MAGNITUDE: f32 = 8.0;
+
+#[derive(Resource, Default)]
+struct ScreenShake {
+    remaining: f32,
+    magnitude: f32,
+}
+
+impl ScreenShake {
+    fn start(&mut self) {
+        self.remaining = SCREEN_SHAKE_SECONDS;
+        self.magnitude = SCREEN_SHAKE_MAGNITUDE;
+    }
+}
+
+fn camera_shake_system(
+    time: Res<Time>,
+    mut shake: ResMut<ScreenShake>,
+    mut cameras: Query<(&mut Transform, &CameraHome)>,
+) {
+    if shake.remaining <= 0.0 {
+        return;
+    }
+    shake.remaining = (shake.remaining - time.delta_secs()).max(0.0);
+    // The offset shrinks linearly as the shake runs out, ending exactly at home
+    let strength = shake.magnitude * shake.remaining / SCREEN_SHAKE_SECONDS;
+    let offset = Vec2::new(fastrand::f32() * 2.0 - 1.0, fastrand::f32() * 2.0 - 1.0) * strength;
+    for (mut transform, home) in &mut cameras {
+        transform.translation = home.0 + offset.extend(0.0);
+    }
+}
+
+// Trait to define the sprite path, so we can use it in generic systems
+trait HasSpritePath {
+    fn sprite_path() -> &'static str;
+}
+
+#[derive(Component, Default)]
+struct Present;
+impl HasSpritePath for Present {
+    fn sprite_path() -> &'static str { "present.png" }
+}
+// Rarer present worth more points
+#[derive(Component, Default)]
+struct GoldPresent;
+impl HasSpritePath for GoldPresent {
+    fn sprite_path() -> &'static str { "gold_present.png" }
+}
+#[derive(Component, Default)]
+struct Snowflake;
+impl HasSpritePath for Snowflake {
+    fn sprite_path() -> &'static str { "snowflake.png" }
+}
+// Gives back a lost life when collected
+#[derive(Component, Default)]
+struct HeartPickup;
+impl HasSpritePath for HeartPickup {
+    fn sprite_path() -> &'static str { "heart.png" }
+}
+// Grants Santa a shield that absorbs the next hit
+#[derive(Component, Default)]
+struct ShieldPickup;
+impl HasSpritePath for ShieldPickup {
+    fn sprite_path() -> &'static str { "shield.png" }
+}
+// Slows every automover down for a while
+#[derive(Component, Default)]
+struct SlowMoPickup;
+impl HasSpritePath for SlowMoPickup {
+    fn sprite_path() -> &'static str { "slowmo.png" }
+}
+// Stops every snowflake in place for a while
+#[derive(Component, Default)]
+struct FreezePickup;
+impl HasSpritePath for FreezePickup {
+    fn sprite_path() -> &'static str { "freeze.png" }
+}
+// Pulls nearby presents towards Santa for a while
+#[derive(Component, Default)]
+struct MagnetPickup;
+impl HasSpritePath for MagnetPickup {
+    fn sprite_path() -> &'static str { "magnet.png" }
+}
+// Makes Santa alone faster for a while
+#[derive(Component, Default)]
+struct BoostPickup;
+impl HasSpritePath for BoostPickup {
+    fn sprite_path() -> &'static str { "boost.png" }
+}
+// Blows away the snowflakes around Santa
+#[derive(Component, Default)]
+struct BombPickup;
+impl HasSpritePath for BombPickup {
+    fn sprite_path() -> &'static str { "bomb.png" }
+}
+// Numbered bonus collectible, see `StreakProgress`
+#[derive(Component, Default)]
+struct CandyCane(u8);
+impl HasSpritePath for CandyCane {
+    fn sprite_path() -> &'static str { "candy_cane.png" }
+}
+// Calls a reindeer to help Santa for the rest of the run
+#[derive(Component, Default)]
+struct ReindeerPickup;
+impl HasSpritePath for ReindeerPickup {
+    fn sprite_path() -> &'static str { "reindeer.png" }
+}
+
+#[derive(Component)]
+struct AutoMoving(Vec2);
+#[derive(Component)]
+struct ColliderCircle(f32);
+
+// Rotation speed in radians per second
+#[derive(Component)]
+struct Spin(f32);
+
+// Trait to let automovers opt into spinning, presents stay upright
+trait Spinning {
+    fn random_spin(_rng: &mut fastrand::Rng) -> Option<Spin> {
+        None
+    }
+}
+impl Spinning for Present {}
+impl Spinning for GoldPresent {}
+impl Spinning for HeartPickup {}
+impl Spinning for ShieldPickup {}
+impl Spinning for SlowMoPickup {}
+impl Spinning for FreezePickup {}
+impl Spinning for MagnetPickup {}
+impl Spinning for BoostPickup {}
+impl Spinning for BombPickup {}
+impl Spinning for CandyCane {}
+impl Spinning for ReindeerPickup {}
+impl Spinning for Snowflake {
+    fn random_spin(rng: &mut fastrand::Rng) -> Option<Spin> {
+        let speed = 0.5 + rng.f32() * 2.0;
+        Some(Spin(if rng.bool() { speed } else { -speed }))
+    }
+}
+
+fn spin_system(
+    time: Res<Time>,
+    mut spinners: Query<(&mut Transform, &Spin)>,
+) {
+    for (mut transform, spin) in spinners.iter_mut() {
+        transform.rotate_z(spin.0 * time.delta_secs());
+    }
+}
+
+// Chance of any spawned present to be a golden one
+const GOLD_PRESENT_CHANCE: f32 = 0.15;
+const GOLD_PRESENT_POINTS: u32 = 5;
+// Presents come in different sizes, scaling both the 32px sprite and the collider
+const PRESENT_MIN_SCALE: f32 = 0.75;
+const PRESENT_MAX_SCALE: f32 = 1.5;
+
+// What a present is worth once delivered, before the combo multiplier
+#[derive(Component)]
+struct PointValue(u32);
+
+// How many automovers of a type should be on the board at the given level
+trait SpawnCount {
+    fn spawn_count(difficulty: Difficulty, level: u32) -> usize;
+}
+impl SpawnCount for Present {
+    fn spawn_count(difficulty: Difficulty, level: u32) -> usize { difficulty.presents() + 2 * (level as usize - 1) }
+}
+impl SpawnCount for Snowflake {
+    fn spawn_count(difficulty: Difficulty, level: u32) -> usize { difficulty.snowflakes() + 2 * (level as usize - 1) }
+}
+
+fn initialize_presents(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    difficulty: Res<Difficulty>,
+    level: Res<Level>,
+) {
+    spawn_presents(&mut commands, &asset_server, &mut rng.0, &bounds, &free_zone, Present::spawn_count(*difficulty, level.0));
+}
+
+fn spawn_presents(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    rng: &mut fastrand::Rng,
+    bounds: &GameBounds,
+    free_zone: &FreeZone,
+    count: usize,
+) {
+    for _ in 0..count {
+        let scale = random_present_scale(rng);
+        let position = random_spawn_position(rng, bounds, free_zone, present_radius(scale));
+        spawn_present(commands, asset_server, rng, position, scale);
+    }
+}
+
+// The size is picked before the position, so the position can keep the whole present on screen
+fn random_present_scale(rng: &mut fastrand::Rng) -> f32 {
+    PRESENT_MIN_SCALE + rng.f32() * (PRESENT_MAX_SCALE - PRESENT_MIN_SCALE)
+}
+
+// Half of the scaled 32px sprite
+fn present_radius(scale: f32) -> f32 {
+    16. * scale
+}
+
+// Every present has a small chance to come out golden and worth more
+fn spawn_present(commands: &mut Commands, asset_server: &AssetServer, rng: &mut fastrand::Rng, position: Vec2, scale: f32) {
+    let (present, points) = if rng.f32() < GOLD_PRESENT_CHANCE {
+        (spawn_automover::<GoldPresent>(commands, asset_server, rng, position), GOLD_PRESENT_POINTS)
+    } else {
+        (spawn_automover::<Present>(commands, asset_server, rng, position), 1)
+    };
+    commands.entity(present).insert((
+        Transform::from_translation(position.extend(0.0)).with_scale(Vec3::splat(scale)),
+        ColliderCircle(present_radius(scale)),
+        PointValue(present_points(points, scale)),
+        PresentLifetime::default(),
+    ));
+}
+
+// A present left alone for too long blinks for a while and then moves somewhere else,
+// so camping in the safest corner and waiting for presents to drift by does not pay off
+const PRESENT_LIFETIME_SECONDS: f32 = 20.0;
+const PRESENT_BLINK_SECONDS: f32 = 3.0;
+const PRESENT_BLINK_INTERVAL: f32 = 0.2;
+
+// Unlike `Lifetime` the present is never despawned, it only relocates, so the board can always be cleared
+#[derive(Component)]
+struct PresentLifetime {
+    timer: Timer,
+    blink: Timer,
+}
+
+impl Default for PresentLifetime {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(PRESENT_LIFETIME_SECONDS, TimerMode::Once),
+            blink: Timer::from_seconds(PRESENT_BLINK_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn relocate_expired_presents_system(
+    time: Res<Time>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    santa: Query<&Transform, With<Santa>>,
+    mut presents: Query<(&mut PresentLifetime, &mut Transform, &mut AutoMoving, &mut Visibility, &ColliderCircle), (Without<Collided>, Without<Santa>)>,
+) {
+    let Ok(santa_transform) = santa.get_single() else {
+        return;
+    };
+    let santa_position = santa_transform.translation.truncate();
+    for (mut lifetime, mut transform, mut automover, mut visibility, collider) in presents.iter_mut() {
+        lifetime.timer.tick(time.delta());
+        if lifetime.timer.finished() {
+            let position = random_spawn_position_away_from(&mut rng.0, &bounds, &free_zone, collider.0, santa_position);
+            transform.translation = position.extend(transform.translation.z);
+            automover.0 = random_direction(&mut rng.0);
+            *lifetime = PresentLifetime::default();
+            *visibility = Visibility::Inherited;
+        } else if lifetime.timer.remaining_secs() <= PRESENT_BLINK_SECONDS && lifetime.blink.tick(time.delta()).just_finished() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+}
+
+// Smaller presents are harder to catch, so the points grow as the size shrinks, rounded up
+fn present_points(points: u32, scale: f32) -> u32 {
+    (points as f32 / scale).ceil() as u32
+}
+
+// Every spawn position, direction and roll is drawn from here. Launching twice with the same `--seed`
+// replays the same sequence, so the presents and snowflakes of the first run land in the same places
+// with the same directions, and so does every run after it as long as the same things happen in them.
+// Purely cosmetic randomness, like particles and screen shake, stays on the global RNG.
+#[derive(Resource)]
+struct GameRng(fastrand::Rng);
+
+impl Default for GameRng {
+    // Without a seed every launch plays out differently
+    fn default() -> Self {
+        Self(fastrand::Rng::new())
+    }
+}
+
+impl GameRng {
+    fn with_seed(seed: u64) -> Self {
+        Self(fastrand::Rng::with_seed(seed))
+    }
+}
+
+fn spawn_automover<T: Component + Default + HasSpritePath + Spinning>(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    rng: &mut fastrand::Rng,
+    position: Vec2,
+) -> Entity {
+    let direction = random_direction(rng);
+
+    let mut automover = commands.spawn((
+        RunEntity,
+        T::default(),
+        Transform::from_xyz(position.x, position.y, 0.0),
+        Sprite::from_image(asset_server.load(T::sprite_path())),
+        AutoMoving(direction),
+        ColliderCircle(16.),
+    ));
+    if let Some(spin) = T::random_spin(rng) {
+        automover.insert(spin);
+    }
+    automover.id()
+}
+
+// Select a random direction from all four quadrants
+fn random_direction(rng: &mut fastrand::Rng) -> Vec2 {
+    direction_from_samples(|| Vec2::new(rng.f32() * 2.0 - 1.0, rng.f32() * 2.0 - 1.0))
+}
+
+// Resamples the rare zero vector, normalizing it would give NaN and an automover that never moves
+fn direction_from_samples(mut sample: impl FnMut() -> Vec2) -> Vec2 {
+    loop {
+        if let Some(direction) = sample().try_normalize() {
+            break direction;
+        }
+    }
+}
+
+// Automovers spawned mid-run keep at least this distance from Santa, so they never hit him instantly
+const SPAWN_DISTANCE_FROM_SANTA: f32 = 100.0;
+
+// Like `random_spawn_position`, but also keeps away from Santa who may have left the FreeZone
+fn random_spawn_position_away_from(rng: &mut fastrand::Rng, bounds: &GameBounds, free_zone: &FreeZone, margin: f32, santa_position: Vec2) -> Vec2 {
+    (0..SPAWN_ATTEMPTS)
+        .map(|_| random_spawn_position(rng, bounds, free_zone, margin))
+        .find(|position| position.distance(santa_position) > SPAWN_DISTANCE_FROM_SANTA)
+        .unwrap_or_else(|| farthest_corner_from(bounds.size(), margin, santa_position))
+}
+
+// The spawnable corner farthest from the given position, where a spawn goes when no random one fits
+fn farthest_corner_from(size: Vec2, margin: f32, position: Vec2) -> Vec2 {
+    let low = Vec2::splat(margin);
+    let high = size - margin;
+    Vec2::new(
+        if position.x - low.x > high.x - position.x { low.x } else { high.x },
+        if position.y - low.y > high.y - position.y { low.y } else { high.y },
+    )
+}
+
+// In survival snowflakes start spawning slowly, but every spawn shortens the interval down to the floor
+const SURVIVAL_SPAWN_INTERVAL: f32 = 4.0;
+const SURVIVAL_SPAWN_INTERVAL_STEP: f32 = 0.1;
+const SURVIVAL_MIN_SPAWN_INTERVAL: f32 = 1.0;
+const SURVIVAL_MAX_SNOWFLAKES: usize = 100;
+
+// Snowflakes are not spawned all at once, but one by one to make the run harder over time
+#[derive(Resource)]
+struct SnowflakeSpawner {
+    timer: Timer,
+    interval: f32,
+}
+
+impl SnowflakeSpawner {
+    fn new(interval: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(interval, TimerMode::Repeating),
+            interval,
+        }
+    }
+}
+
+// Limit of snowflakes on the board at the same time
+#[derive(Resource)]
+struct MaxSnowflakes(usize);
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_snowflake_over_time_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    game_mode: Res<GameMode>,
+    mut spawner: ResMut<SnowflakeSpawner>,
+    max_snowflakes: Res<MaxSnowflakes>,
+    snowflakes: Query<(), With<Snowflake>>,
+    santa: Query<&Transform, With<Santa>>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() || snowflakes.iter().count() >= max_snowflakes.0 {
+        return;
+    }
+    let santa_position = santa.single().translation.truncate();
+    let position = if *game_mode == GameMode::Survival {
+        spawner.interval = (spawner.interval - SURVIVAL_SPAWN_INTERVAL_STEP).max(SURVIVAL_MIN_SPAWN_INTERVAL);
+        let interval = spawner.interval;
+        spawner.timer.set_duration(Duration::from_secs_f32(interval));
+        random_edge_position_away_from(&mut rng.0, &bounds, santa_position)
+    } else {
+        random_spawn_position_away_from(&mut rng.0, &bounds, &free_zone, SPAWN_MARGIN, santa_position)
+    };
+    spawn_automover::<Snowflake>(&mut commands, &asset_server, &mut rng.0, position);
+}
+
+// Pickups show up every so often, as long as there is not one of the same kind on the board already
+trait Pickup {
+    const INTERVAL: f32;
+    // How many can show up in a single run, `None` for no limit
+    const MAX_PER_RUN: Option<u32> = None;
+    // Keeps coming past the limit while a snowman is around, so it can always be worn down
+    const UNCAPPED_WHILE_SNOWMAN: bool = false;
+}
+impl Pickup for HeartPickup {
+    const INTERVAL: f32 = 20.0;
+}
+impl Pickup for ShieldPickup {
+    const INTERVAL: f32 = 30.0;
+}
+impl Pickup for SlowMoPickup {
+    const INTERVAL: f32 = 25.0;
+}
+impl Pickup for FreezePickup {
+    const INTERVAL: f32 = 35.0;
+}
+impl Pickup for MagnetPickup {
+    const INTERVAL: f32 = 30.0;
+}
+impl Pickup for BoostPickup {
+    const INTERVAL: f32 = 20.0;
+}
+impl Pickup for BombPickup {
+    const INTERVAL: f32 = 15.0;
+    const MAX_PER_RUN: Option<u32> = Some(1);
+    const UNCAPPED_WHILE_SNOWMAN: bool = true;
+}
+impl Pickup for ReindeerPickup {
+    const INTERVAL: f32 = 25.0;
+    const MAX_PER_RUN: Option<u32> = Some(1);
+}
+
+#[derive(Resource)]
+struct PickupSpawner<T> {
+    timer: Timer,
+    spawned: u32,
+    _pickup: PhantomData<T>,
+}
+
+impl<T: Pickup> Default for PickupSpawner<T> {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(T::INTERVAL, TimerMode::Repeating),
+            spawned: 0,
+            _pickup: PhantomData,
+        }
+    }
+}
+
+fn reset_pickup_spawner_system<T: Pickup + Send + Sync + 'static>(mut spawner: ResMut<PickupSpawner<T>>) {
+    spawner.timer.reset();
+    spawner.spawned = 0;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_pickup_over_time_system<T: Component + Default + HasSpritePath + Spinning + Pickup>(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    mut spawner: ResMut<PickupSpawner<T>>,
+    pickups: Query<(), With<T>>,
+    santa: Query<&Transform, With<Santa>>,
+    snowmen: Query<(), With<Snowman>>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() || !pickups.is_empty() {
+        return;
+    }
+    let uncapped = T::UNCAPPED_WHILE_SNOWMAN && !snowmen.is_empty();
+    if !uncapped && T::MAX_PER_RUN.is_some_and(|max| spawner.spawned >= max) {
+        return;
+    }
+    spawner.spawned += 1;
+    let santa_position = santa.single().translation.truncate();
+    let position = random_spawn_position_away_from(&mut rng.0, &bounds, &free_zone, SPAWN_MARGIN, santa_position);
+    spawn_automover::<T>(&mut commands, &asset_server, &mut rng.0, position);
+}
+
+const BOMB_RADIUS: f32 = 200.0;
+const BOMB_POINTS_PER_SNOWFLAKE: u32 = 1;
+const BOMB_FLASH_SECONDS: f32 = 0.3;
+
+// How far behind Santa the reindeer trots, measured along his path
+const REINDEER_FOLLOW_DISTANCE: f32 = 40.0;
+const REINDEER_DASH_INTERVAL: f32 = 10.0;
+const REINDEER_DASH_SPEED: f32 = 300.0;
+
+// Follows Santa around and every now and then dashes off to grab the nearest present. It is not a `Santa`,
+// so the collision detection, which only checks Santa's collider, never lets snowflakes hit it.
+#[derive(Component)]
+struct Reindeer {
+    // Santa's recent positions, newest first, trimmed to the follow distance
+    trail: VecDeque<Vec2>,
+    dash_timer: Timer,
+    target: Option<Entity>,
+    // Every present is worth half a point, so the score only goes up on every second one
+    presents_collected: u32,
+}
+
+impl Reindeer {
+    fn new() -> Self {
+        Self {
+            trail: VecDeque::new(),
+            dash_timer: Timer::from_seconds(REINDEER_DASH_INTERVAL, TimerMode::Repeating),
+            target: None,
+            presents_collected: 0,
+        }
+    }
+
+    // Remembers where Santa went and returns the spot the follow distance behind him
+    fn follow_point(&mut self, santa_position: Vec2) -> Vec2 {
+        if self.trail.front().is_none_or(|last| last.distance(santa_position) >= 1.0) {
+            self.trail.push_front(santa_position);
+        }
+        let mut travelled = 0.0;
+        for i in 1..self.trail.len() {
+            travelled += self.trail[i - 1].distance(self.trail[i]);
+            if travelled >= REINDEER_FOLLOW_DISTANCE {
+                self.trail.truncate(i + 1);
+                return self.trail[i];
+            }
+        }
+        *self.trail.back().unwrap()
+    }
+}
+
+fn spawn_reindeer_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    santa: Query<&Transform, With<Santa>>,
+) {
+    commands.spawn((
+        RunEntity,
+        Reindeer::new(),
+        Transform::from_translation(santa.single().translation.truncate().extend(0.0)),
+        Sprite::from_image(asset_server.load("reindeer.png")),
+        ColliderCircle(16.),
+    ));
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn reindeer_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    game_mode: Res<GameMode>,
+    win_condition: Res<WinCondition>,
+    difficulty: Res<Difficulty>,
+    mut score: ResMut<Score>,
+    mut speed: ResMut<Speed>,
+    mut run_stats: ResMut<RunStats>,
+    santa: Query<&Transform, With<Santa>>,
+    mut reindeer: Query<(&mut Reindeer, &mut Transform, &ColliderCircle), Without<Santa>>,
+    presents: Query<(Entity, &Transform, &ColliderCircle), (Or<(With<Present>, With<GoldPresent>)>, Without<Collided>, Without<Reindeer>)>,
+) {
+    let Ok(santa_transform) = santa.get_single() else {
+        return;
+    };
+    let santa_position = santa_transform.translation.truncate();
+    for (mut reindeer, mut transform, collider) in reindeer.iter_mut() {
+        let follow_point = reindeer.follow_point(santa_position);
+        let position = transform.translation.truncate();
+        if reindeer.dash_timer.tick(time.delta()).just_finished() {
+            reindeer.target = presents
+                .iter()
+                .min_by(|(_, a, _), (_, b, _)| {
+                    a.translation.truncate().distance_squared(position).total_cmp(&b.translation.truncate().distance_squared(position))
+                })
+                .map(|(entity, _, _)| entity);
+        }
+        // Back to following once the present is gone, whoever took it
+        let Some((target, target_transform, target_collider)) = reindeer.target.and_then(|target| presents.get(target).ok()) else {
+            reindeer.target = None;
+            // Same speed on the way back, so it never teleports behind Santa after a dash
+            let position = position.move_towards(follow_point, REINDEER_DASH_SPEED * time.delta_secs());
+            transform.translation = position.extend(transform.translation.z);
+            continue;
+        };
+        let target_position = target_transform.translation.truncate();
+        let position = position.move_towards(target_position, REINDEER_DASH_SPEED * time.delta_secs());
+        transform.translation = position.extend(transform.translation.z);
+        if !circles_overlap(position, collider.0, target_position, target_collider.0, 1.0) {
+            continue;
+        }
+        commands.entity(target).despawn_recursive();
+        reindeer.target = None;
+        reindeer.presents_collected += 1;
+        // Counts like a present Santa picked up himself
+        speed.0 += difficulty.speed_increment();
+        run_stats.presents_collected += 1;
+        if reindeer.presents_collected.is_multiple_of(2) {
+            score.0 += 1;
+            spawn_floating_text(&mut commands, target_position, "+1".to_string());
+        }
+        if game_mode.respawns_presents(*win_condition) {
+            let scale = random_present_scale(&mut rng.0);
+            let position = random_spawn_position_away_from(&mut rng.0, &bounds, &free_zone, present_radius(scale), santa_position);
+            spawn_present(&mut commands, &asset_server, &mut rng.0, position, scale);
+        }
+    }
+}
+
+const CANDY_CANE_COUNT: u8 = 3;
+const CANDY_CANE_BONUS: u32 = 10;
+
+// How many candy canes were collected in order so far, the next one to grab is one higher
+#[derive(Resource, Default)]
+struct StreakProgress(u8);
+
+#[derive(Component)]
+struct UiCandyCaneText;
+
+fn initialize_candy_canes(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    mut streak: ResMut<StreakProgress>,
+) {
+    streak.0 = 0;
+    spawn_candy_canes(&mut commands, &asset_server, &mut rng.0, &bounds, &free_zone);
+}
+
+fn spawn_candy_canes(commands: &mut Commands, asset_server: &AssetServer, rng: &mut fastrand::Rng, bounds: &GameBounds, free_zone: &FreeZone) {
+    for number in 1..=CANDY_CANE_COUNT {
+        let position = random_spawn_position(rng, bounds, free_zone, SPAWN_MARGIN);
+        let cane = spawn_automover::<CandyCane>(commands, asset_server, rng, position);
+        commands.entity(cane).insert(CandyCane(number)).with_children(|parent| {
+            parent.spawn((
+                Text2d::new(number.to_string()),
+                TextFont { font_size: 14.0, ..default() },
+                Transform::from_xyz(0.0, 0.0, 1.0),
+            ));
+        });
+    }
+}
+
+// The canes are only despawned in PostUpdate, so the number of the one hit can still be read here.
+// Completing the set pays the bonus, grabbing one out of order resets the streak with a fresh set.
+#[allow(clippy::too_many_arguments)]
+fn collect_candy_canes_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    mut score: ResMut<Score>,
+    mut streak: ResMut<StreakProgress>,
+    mut event_reader: EventReader<CollisionEvent>,
+    canes: Query<&CandyCane>,
+    remaining: Query<Entity, (With<CandyCane>, Without<Collided>)>,
+) {
+    for event in event_reader.read().filter(|event| event.kind == CollisionKind::CandyCane) {
+        let Ok(cane) = canes.get(event.entity) else {
+            continue;
+        };
+        if cane.0 == streak.0 + 1 {
+            streak.0 += 1;
+            if streak.0 == CANDY_CANE_COUNT {
+                score.0 += CANDY_CANE_BONUS;
+                spawn_floating_text(&mut commands, event.position, format!("+{CANDY_CANE_BONUS}"));
+            }
+        } else {
+            streak.0 = 0;
+            for entity in remaining.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            spawn_candy_canes(&mut commands, &asset_server, &mut rng.0, &bounds, &free_zone);
+        }
+    }
+}
+
+fn update_candy_cane_ui(
+    streak: Res<StreakProgress>,
+    mut query: Query<&mut Text, With<UiCandyCaneText>>,
+) {
+    for mut text in query.iter_mut() {
+        text.0 = if streak.0 >= CANDY_CANE_COUNT {
+            "Candy canes complete!".to_string()
+        } else {
+            format!("Next candy cane: {}", streak.0 + 1)
+        };
+    }
+}
+
+// Clears every snowflake touching the blast circle around Santa. Only presents decide the outcome of a run,
+// and the snowflake spawner simply refills the board over time, so nothing else has to know about it.
+#[allow(clippy::type_complexity)]
+fn detonate_bomb_system(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    game_mode: Res<GameMode>,
+    grid: Res<SpatialGrid>,
+    santa: Query<&Transform, With<Santa>>,
+    // Snowflakes that already hit Santa this frame are despawned by their own collision
+    snowflakes: Query<(&Transform, &ColliderCircle), (With<Snowflake>, Without<Collided>)>,
+    mut snowmen: Query<(Entity, &Transform, &ColliderCircle, &mut Snowman)>,
+) {
+    let santa_position = santa.single().translation.truncate();
+    let mut destroyed = 0;
+    for entity in grid.entities_near(santa_position, BOMB_RADIUS + grid.max_radius) {
+        let Ok((transform, collider)) = snowflakes.get(entity) else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+        if !circles_overlap(santa_position, BOMB_RADIUS, position, collider.0, 1.0) {
+            continue;
+        }
+        commands.entity(entity).despawn();
+        commands.spawn((
+            RunEntity,
+            Lifetime(Timer::from_seconds(BOMB_FLASH_SECONDS, TimerMode::Once)),
+            Sprite::from_color(Color::WHITE, Vec2::splat(32.0)),
+            Transform::from_translation(position.extend(1.0)),
+        ));
+        spawn_particle_burst(&mut commands, position, Color::WHITE);
+        destroyed += 1;
+    }
+    let mut bonus = destroyed * BOMB_POINTS_PER_SNOWFLAKE;
+    // The snowman takes a single hit per bomb
+    for (entity, transform, collider, mut snowman) in snowmen.iter_mut() {
+        let position = transform.translation.truncate();
+        if !circles_overlap(santa_position, BOMB_RADIUS, position, collider.0, 1.0) {
+            continue;
+        }
+        snowman.hp = snowman.hp.saturating_sub(1);
+        spawn_particle_burst(&mut commands, position, Color::WHITE);
+        if snowman.hp == 0 {
+            commands.entity(entity).despawn_recursive();
+            bonus += SNOWMAN_DEFEAT_POINTS;
+            spawn_floating_text(&mut commands, position, format!("+{SNOWMAN_DEFEAT_POINTS}"));
+        }
+    }
+    // In survival the score is the time survived, bonus points would just be overwritten
+    if *game_mode != GameMode::Survival {
+        score.0 += bonus;
+    }
+}
+
+// The snowman boss shows up once per run when the score reaches this
+const SNOWMAN_SCORE_THRESHOLD: u32 = 5;
+const SNOWMAN_HP: u32 = 3;
+const SNOWMAN_SPEED: f32 = 40.0;
+const SNOWMAN_DAMAGE: u32 = 2;
+const SNOWMAN_DEFEAT_POINTS: u32 = 5;
+const SNOWMAN_BANNER_SECONDS: f32 = 3.0;
+
+// A slow boss that keeps walking towards Santa, only bombs can wear it down
+#[derive(Component)]
+struct Snowman {
+    hp: u32,
+}
+
+#[derive(Resource, Default)]
+struct SnowmanSpawned(bool);
+
+fn reset_snowman_system(mut snowman_spawned: ResMut<SnowmanSpawned>) {
+    snowman_spawned.0 = false;
+}
+
+fn spawn_snowman_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    mut rng: ResMut<GameRng>,
+    score: Res<Score>,
+    mut snowman_spawned: ResMut<SnowmanSpawned>,
+    santa: Query<&Transform, With<Santa>>,
+) {
+    if snowman_spawned.0 || score.0 < SNOWMAN_SCORE_THRESHOLD {
+        return;
+    }
+    snowman_spawned.0 = true;
+    let position = random_edge_position_away_from(&mut rng.0, &bounds, santa.single().translation.truncate());
+    commands.spawn((
+        RunEntity,
+        Snowman { hp: SNOWMAN_HP },
+        Transform::from_translation(position.extend(0.0)),
+        Sprite::from_image(asset_server.load("snowman.png")),
+        ColliderCircle(24.),
+    ));
+    // Announce it in big letters, so it does not catch the player off guard
+    commands.spawn((
+        RunEntity,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Percent(20.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Toast(Timer::from_seconds(SNOWMAN_BANNER_SECONDS, TimerMode::Once)),
+    )).with_child((
+        Text::new("A snowman is coming! Bomb it three times"),
+        TextFont { font_size: 36.0, ..default() },
+        TextColor(Color::srgb(0.7, 0.9, 1.0)),
+    ));
+}
+
+// Walks straight at Santa, its own movement rather than `AutoMoving`, so it never bounces around
+fn snowman_seek_system(
+    time: Res<Time>,
+    santa: Query<&Transform, With<Santa>>,
+    mut snowmen: Query<&mut Transform, (With<Snowman>, Without<Santa>)>,
+) {
+    let Ok(santa_transform) = santa.get_single() else {
+        return;
+    };
+    let santa_position = santa_transform.translation.truncate();
+    for mut transform in snowmen.iter_mut() {
+        let position = transform.translation.truncate().move_towards(santa_position, SNOWMAN_SPEED * time.delta_secs());
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+// A sleigh flies across the screen this often, telegraphed by an arrow at the edge it comes from
+const SLEIGH_HAZARD_INTERVAL: f32 = 15.0;
+const SLEIGH_HAZARD_WARNING_SECONDS: f32 = 1.0;
+// Relative to the current automover `Speed`
+const SLEIGH_HAZARD_SPEED_FACTOR: f32 = 2.0;
+const SLEIGH_HAZARD_RADIUS: f32 = 24.0;
+const SLEIGH_HAZARD_ARROW_INSET: f32 = 20.0;
+// Reddish, so it is not mistaken for the sleigh the presents are delivered to
+const SLEIGH_HAZARD_TINT: Color = Color::srgb(1.0, 0.55, 0.55);
+
+// Counts down to the next pass, ticking only while playing, so it pauses with everything else
+#[derive(Resource)]
+struct SleighHazardTimer(Timer);
+
+impl Default for SleighHazardTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(SLEIGH_HAZARD_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+fn reset_sleigh_hazard_system(mut sleigh_hazard_timer: ResMut<SleighHazardTimer>) {
+    *sleigh_hazard_timer = SleighHazardTimer::default();
+}
+
+// The arrow shown before a pass, `heading` is 1 for flying right and -1 for flying left
+#[derive(Component)]
+struct SleighHazardWarning {
+    timer: Timer,
+    heading: f32,
+}
+
+// Flies straight across at a fixed height, passing over everything but Santa
+#[derive(Component)]
+struct SleighHazard {
+    heading: f32,
+}
+
+fn spawn_sleigh_hazard_warning_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    mut rng: ResMut<GameRng>,
+    mut sleigh_hazard_timer: ResMut<SleighHazardTimer>,
+) {
+    if !sleigh_hazard_timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let heading = if rng.0.bool() { 1.0 } else { -1.0 };
+    let y = SLEIGH_HAZARD_RADIUS + rng.0.f32() * (bounds.height - 2.0 * SLEIGH_HAZARD_RADIUS).max(0.0);
+    let x = if heading > 0.0 { SLEIGH_HAZARD_ARROW_INSET } else { bounds.width - SLEIGH_HAZARD_ARROW_INSET };
+    commands.spawn((
+        RunEntity,
+        SleighHazardWarning {
+            timer: Timer::from_seconds(SLEIGH_HAZARD_WARNING_SECONDS, TimerMode::Once),
+            heading,
+        },
+        Transform::from_xyz(x, y, 1.0),
+        Sprite {
+            image: asset_server.load("arrow.png"),
+            // The arrow points right, the way the sleigh is going to fly
+            flip_x: heading < 0.0,
+            ..default()
+        },
+    ));
+}
+
+// Blinks the warning and launches the sleigh from just outside the edge once it runs out
+fn sleigh_hazard_warning_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    mut warnings: Query<(Entity, &mut SleighHazardWarning, &Transform, &mut Visibility)>,
+) {
+    for (entity, mut warning, transform, mut visibility) in warnings.iter_mut() {
+        if !warning.timer.tick(time.delta()).finished() {
+            let blink_on = ((warning.timer.elapsed_secs() * 8.0) as u32).is_multiple_of(2);
+            *visibility = if blink_on { Visibility::Inherited } else { Visibility::Hidden };
+            continue;
+        }
+        commands.entity(entity).despawn();
+        let x = if warning.heading > 0.0 { -SLEIGH_HAZARD_RADIUS } else { bounds.width + SLEIGH_HAZARD_RADIUS };
+        commands.spawn((
+            RunEntity,
+            SleighHazard { heading: warning.heading },
+            Transform::from_xyz(x, transform.translation.y, 1.0),
+            Sprite {
+                image: asset_server.load(Sleigh::sprite_path()),
+                color: SLEIGH_HAZARD_TINT,
+                flip_x: warning.heading < 0.0,
+                ..default()
+            },
+            ColliderCircle(SLEIGH_HAZARD_RADIUS),
+        ));
+    }
+}
+
+// Its own movement rather than `AutoMoving`, so it never bounces and leaves through the far side
+fn move_sleigh_hazard_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<Speed>,
+    bounds: Res<GameBounds>,
+    mut sleighs: Query<(Entity, &SleighHazard, &mut Transform)>,
+) {
+    for (entity, sleigh, mut transform) in sleighs.iter_mut() {
+        transform.translation.x += sleigh.heading * speed.0 * SLEIGH_HAZARD_SPEED_FACTOR * time.delta_secs();
+        let x = transform.translation.x;
+        if (sleigh.heading > 0.0 && x > bounds.width + SLEIGH_HAZARD_RADIUS) || (sleigh.heading < 0.0 && x < -SLEIGH_HAZARD_RADIUS) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Absorbs the next snowflake hit instead of a life
+#[derive(Component)]
+struct Shield;
+
+// How long the ring of a broken shield takes to expand and fade away
+const SHIELD_BREAK_SECONDS: f32 = 0.4;
+
+#[derive(Component)]
+struct ShieldBreak(Timer);
+
+#[derive(Component)]
+struct UiShieldIndicator;
+
+// Santa turns blue while the shield is up
+const SHIELD_TINT: Color = Color::srgb(0.5, 0.7, 1.0);
+// Only one shield can be held, another one picked up meanwhile is worth a point
+const SPARE_SHIELD_SCORE: u32 = 1;
+
+fn grant_shield_system(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    mut event_reader: EventReader<CollisionEvent>,
+    mut santa: Query<(Entity, Has<Shield>, &mut Sprite), With<Santa>>,
+) {
+    let (santa, mut shielded, mut sprite) = santa.single_mut();
+    for event in event_reader.read().filter(|event| event.kind == CollisionKind::ShieldPickup) {
+        if shielded {
+            score.0 += SPARE_SHIELD_SCORE;
+            spawn_floating_text(&mut commands, event.position, format!("+{SPARE_SHIELD_SCORE}"));
+            continue;
+        }
+        shielded = true;
+        commands.entity(santa).insert(Shield);
+        // Keep the alpha, Santa may be blinking while invulnerable
+        sprite.color = SHIELD_TINT.with_alpha(sprite.color.alpha());
+    }
+}
+
+fn shield_break_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+    mut santa: Query<(Entity, &Transform, &ColliderCircle, &mut ShieldBreak)>,
+) {
+    for (entity, transform, collider, mut shield_break) in santa.iter_mut() {
+        if shield_break.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<ShieldBreak>();
+            continue;
+        }
+        let progress = shield_break.0.fraction();
+        let radius = collider.0 * (1.5 + progress);
+        gizmos.circle_2d(transform.translation.truncate(), radius, Color::srgba(0.5, 0.8, 1.0, 1.0 - progress));
+    }
+}
+
+fn update_shield_ui(
+    santa: Query<Has<Shield>, With<Santa>>,
+    mut indicators: Query<&mut Visibility, With<UiShieldIndicator>>,
+) {
+    let shielded = santa.single();
+    for mut visibility in indicators.iter_mut() {
+        *visibility = if shielded { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+// Collecting a heart at full lives is not wasted, it is worth some score instead
+const FULL_LIVES_HEART_SCORE: u32 = 2;
+
+fn heal_system(
+    mut commands: Commands,
+    mut lives: ResMut<Lives>,
+    mut score: ResMut<Score>,
+    starting_lives: Res<StartingLives>,
+    mut event_reader: EventReader<CollisionEvent>,
+) {
+    for event in event_reader.read().filter(|event| event.kind == CollisionKind::HeartPickup) {
+        if lives.0 < starting_lives.0 {
+            lives.0 += 1;
+        } else {
+            score.0 += FULL_LIVES_HEART_SCORE;
+            spawn_floating_text(&mut commands, event.position, format!("+{FULL_LIVES_HEART_SCORE}"));
+        }
+    }
+}
+
+// Select a random position along one of the screen edges, away from Santa
+fn random_edge_position_away_from(rng: &mut fastrand::Rng, bounds: &GameBounds, santa_position: Vec2) -> Vec2 {
+    let width = bounds.width;
+    let height = bounds.height;
+    let half_size = 32. / 2.;
+    for _ in 0..SPAWN_ATTEMPTS {
+        let along_x = half_size + rng.f32() * (width - 32.);
+        let along_y = half_size + rng.f32() * (height - 32.);
+        let position = match rng.u8(0..4) {
+            0 => Vec2::new(half_size, along_y),
+            1 => Vec2::new(width - half_size, along_y),
+            2 => Vec2::new(along_x, half_size),
+            _ => Vec2::new(along_x, height - half_size),
+        };
+        if position.distance(santa_position) > SPAWN_DISTANCE_FROM_SANTA {
+            return position;
+        }
+    }
+    // The corners lie on the edges too
+    farthest_corner_from(bounds.size(), half_size, santa_position)
+}
+
+// In survival the score is the number of seconds survived
+fn survival_score_system(
+    game_timer: Res<GameTimer>,
+    mut score: ResMut<Score>,
+) {
+    let seconds = game_timer.0.elapsed_secs() as u32;
+    if score.0 != seconds {
+        score.0 = seconds;
+    }
+}
+
+// Defines the radius in the center of the screen where automovers cannot spawn, follows the difficulty
+#[derive(Resource)]
+struct FreeZone(f32);
+
+impl FreeZone {
+    // Never more than half the smaller window side, otherwise small windows would have nowhere left to spawn
+    fn radius(&self, size: Vec2) -> f32 {
+        self.0.min(size.min_element() / 2.0)
+    }
+}
+
+// Half of the 32px automover sprite, spawns keep this margin from every window edge
+const SPAWN_MARGIN: f32 = 16.0;
+// After this many rejected samples the spawn gives up and takes a corner instead
+const SPAWN_ATTEMPTS: u32 = 100;
+
+// Select a random position that do not fall within the FreeZone in the center
+fn random_spawn_position(rng: &mut fastrand::Rng, bounds: &GameBounds, free_zone: &FreeZone, margin: f32) -> Vec2 {
+    random_spawn_position_in(rng, bounds.size(), free_zone, margin)
+}
+
+// Picks within `[margin, size - margin]` on both axes so the whole sprite stays on screen. The margin is
+// the radius of what is spawned, `SPAWN_MARGIN` for the regular 32px automovers.
+fn random_spawn_position_in(rng: &mut fastrand::Rng, size: Vec2, free_zone: &FreeZone, margin: f32) -> Vec2 {
+    let center = size / 2.0;
+    let free_zone = free_zone.radius(size);
+    for _ in 0..SPAWN_ATTEMPTS {
+        let x = margin + rng.f32() * (size.x - 2.0 * margin);
+        let y = margin + rng.f32() * (size.y - 2.0 * margin);
+        let position = Vec2::new(x, y);
+        if position.distance(center) > free_zone {
+            return position;
+        }
+    }
+    // The free zone covers (nearly) everything, so take the farthest spot from the center. All four
+    // corners are equally far, a random one keeps the fallback spawns from piling up on each other
+    let low = Vec2::splat(margin);
+    let high = size - margin;
+    Vec2::new(
+        if rng.bool() { low.x } else { high.x },
+        if rng.bool() { low.y } else { high.y },
+    )
+}
+
+// `Speed` is a resource becauese all the automvers share the same speed.
+#[derive(Resource)]
+struct Speed(f32);
+
+fn automoving_system(
+    time: Res<Time>,
+    speed: Res<Speed>,
+    slow_mo: Res<SlowMo>,
+    frozen: Res<Frozen>,
+    mut automovers: Query<(&mut Transform, &AutoMoving, Has<Snowflake>)>,
+) {
+    // Only the automovers slow down, Santa keeps his own speed
+    let speed = speed.0 * slow_mo.speed_scale();
+    for (mut transform, automover, is_snowflake) in automovers.iter_mut() {
+        // Frozen snowflakes keep their direction, so they carry on the same way once thawed
+        if is_snowflake && frozen.active() {
+            continue;
+        }
+        let direction = automover.0;
+        transform.translation.x += direction.x * speed * time.delta_secs();
+        transform.translation.y += direction.y * speed * time.delta_secs();
+    }
+}
+
+const SLOW_MO_SECONDS: f32 = 5.0;
+const SLOW_MO_FACTOR: f32 = 0.5;
+
+// Slows the automovers down while the timer runs
+#[derive(Resource)]
+struct SlowMo {
+    timer: Timer,
+    factor: f32,
+}
+
+impl Default for SlowMo {
+    fn default() -> Self {
+        // Starts out finished, so nothing is slowed until a pickup is collected
+        let mut timer = Timer::from_seconds(SLOW_MO_SECONDS, TimerMode::Once);
+        timer.set_elapsed(timer.duration());
+        Self { timer, factor: SLOW_MO_FACTOR }
+    }
+}
+
+impl SlowMo {
+    fn speed_scale(&self) -> f32 {
+        if self.timer.finished() { 1.0 } else { self.factor }
+    }
+}
+
+#[derive(Component)]
+struct UiSlowMoTint;
+
+fn reset_slow_mo_system(mut slow_mo: ResMut<SlowMo>) {
+    *slow_mo = SlowMo::default();
+}
+
+// Another pickup while slowed restarts the timer, the factor never compounds
+fn start_slow_mo_system(mut slow_mo: ResMut<SlowMo>) {
+    slow_mo.timer.reset();
+    slow_mo.factor = SLOW_MO_FACTOR;
+}
+
+fn tick_slow_mo_system(
+    time: Res<Time>,
+    mut slow_mo: ResMut<SlowMo>,
+    mut tints: Query<&mut BackgroundColor, With<UiSlowMoTint>>,
+) {
+    slow_mo.timer.tick(time.delta());
+    // The tint fades together with the remaining slow motion
+    let alpha = if slow_mo.timer.finished() { 0.0 } else { 0.15 * slow_mo.timer.fraction_remaining() };
+    for mut tint in tints.iter_mut() {
+        tint.0.set_alpha(alpha);
+    }
+}
+
+const FROZEN_SECONDS: f32 = 5.0;
+const FROZEN_TINT: Color = Color::srgb(0.6, 0.85, 1.0);
+
+// Holds every snowflake in place while the timer runs, presents keep drifting
+#[derive(Resource)]
+struct Frozen(Timer);
+
+impl Default for Frozen {
+    fn default() -> Self {
+        // Starts out finished, same as `SlowMo`
+        let mut timer = Timer::from_seconds(FROZEN_SECONDS, TimerMode::Once);
+        timer.set_elapsed(timer.duration());
+        Self(timer)
+    }
+}
+
+impl Frozen {
+    fn active(&self) -> bool {
+        !self.0.finished()
+    }
+}
+
+fn snowflakes_frozen(frozen: Res<Frozen>) -> bool {
+    frozen.active()
+}
+
+fn reset_frozen_system(mut frozen: ResMut<Frozen>) {
+    *frozen = Frozen::default();
+}
+
+// A second freeze while one is active extends it by a full duration rather than stacking
+fn start_frozen_system(mut frozen: ResMut<Frozen>, mut event_reader: EventReader<CollisionEvent>) {
+    for _ in event_reader.read().filter(|event| event.kind == CollisionKind::FreezePickup) {
+        let remaining = if frozen.active() { frozen.0.remaining() } else { Duration::ZERO };
+        frozen.0.set_duration(remaining + Duration::from_secs_f32(FROZEN_SECONDS));
+        frozen.0.reset();
+    }
+}
+
+fn tick_frozen_system(
+    time: Res<Time>,
+    mut frozen: ResMut<Frozen>,
+    mut snowflakes: Query<(&mut Sprite, Has<Homing>), With<Snowflake>>,
+) {
+    frozen.0.tick(time.delta());
+    // Also catches snowflakes spawned during the freeze, and gives back the homing tint once it thaws
+    for (mut sprite, is_homing) in snowflakes.iter_mut() {
+        sprite.color = if frozen.active() {
+            FROZEN_TINT
+        } else if is_homing {
+            HOMING_TINT
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+const MAGNET_RADIUS: f32 = 180.0;
+const MAGNET_SECONDS: f32 = 6.0;
+// The range circle starts blinking when the magnet is about to run out
+const MAGNET_WARNING_SECONDS: f32 = 1.5;
+
+// Steers every present within the radius towards Santa until the timer runs out
+#[derive(Component)]
+struct Magnet {
+    radius: f32,
+    timer: Timer,
+}
+
+impl Magnet {
+    fn new() -> Self {
+        Self { radius: MAGNET_RADIUS, timer: Timer::from_seconds(MAGNET_SECONDS, TimerMode::Once) }
+    }
+}
+
+// Another magnet while one is active simply starts the timer over
+fn grant_magnet_system(
+    mut commands: Commands,
+    santa: Query<Entity, With<Santa>>,
+) {
+    commands.entity(santa.single()).insert(Magnet::new());
+}
+
+// Runs before `automoving_system`, so `bounce_automovers_system` still has the last word. A present pulled
+// towards Santa standing at a wall gets clamped and slides along it instead of flipping back and forth.
+#[allow(clippy::type_complexity)]
+fn magnet_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut santa: Query<(Entity, &Transform, &mut Magnet), With<Santa>>,
+    mut presents: Query<(&mut AutoMoving, &Transform), (Or<(With<Present>, With<GoldPresent>)>, Without<Santa>)>,
+) {
+    let Ok((santa, santa_transform, mut magnet)) = santa.get_single_mut() else {
+        return;
+    };
+    if magnet.timer.tick(time.delta()).finished() {
+        commands.entity(santa).remove::<Magnet>();
+        return;
+    }
+    let santa_position = santa_transform.translation.truncate();
+    for (mut automover, transform) in presents.iter_mut() {
+        let to_santa = santa_position - transform.translation.truncate();
+        if to_santa.length() <= magnet.radius {
+            // Right on top of Santa there is no direction to pull in, keep the current drift
+            automover.0 = to_santa.normalize_or(automover.0);
+        }
+    }
+}
+
+fn draw_magnet_system(
+    mut gizmos: Gizmos,
+    santa: Query<(&Transform, &Magnet), With<Santa>>,
+) {
+    for (transform, magnet) in santa.iter() {
+        let remaining = magnet.timer.remaining_secs();
+        if remaining < MAGNET_WARNING_SECONDS && ((remaining * 8.0) as u32).is_multiple_of(2) {
+            continue;
+        }
+        gizmos.circle_2d(transform.translation.truncate(), magnet.radius, Color::srgba(1.0, 0.3, 0.3, 0.25));
+    }
+}
+
+// Once the score reaches this a couple of snowflakes start hunting Santa
+const HOMING_SCORE_THRESHOLD: u32 = 7;
+const HOMING_SNOWFLAKES: usize = 2;
+// Radians per second, slow enough that circling around Santa's chaser still shakes it off
+const HOMING_TURN_RATE: f32 = 1.2;
+const HOMING_TINT: Color = Color::srgb(1.0, 0.45, 0.45);
+
+#[derive(Component)]
+struct Homing {
+    turn_rate: f32,
+}
+
+// Homing snowflakes are picked only once per run, however long the score stays above the threshold
+#[derive(Resource, Default)]
+struct HomingConverted(bool);
+
+fn reset_homing_system(mut homing_converted: ResMut<HomingConverted>) {
+    homing_converted.0 = false;
+}
+
+fn convert_homing_snowflakes_system(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    score: Res<Score>,
+    mut homing_converted: ResMut<HomingConverted>,
+    snowflakes: Query<Entity, (With<Snowflake>, Without<Homing>)>,
+) {
+    if homing_converted.0 || score.0 < HOMING_SCORE_THRESHOLD {
+        return;
+    }
+    homing_converted.0 = true;
+    // The tint follows from `tick_frozen_system`, which owns the snowflake colors
+    for snowflake in rng.0.choose_multiple(snowflakes.iter(), HOMING_SNOWFLAKES) {
+        commands.entity(snowflake).insert(Homing { turn_rate: HOMING_TURN_RATE });
+    }
+}
+
+// Rotates the direction towards Santa by at most the turn rate. A wall bounce flips it as usual
+// and the turning simply picks up again from the new direction.
+fn homing_system(
+    time: Res<Time>,
+    santa: Query<&Transform, With<Santa>>,
+    mut homers: Query<(&mut AutoMoving, &Transform, &Homing), Without<Santa>>,
+) {
+    let Ok(santa_transform) = santa.get_single() else {
+        return;
+    };
+    let santa_position = santa_transform.translation.truncate();
+    for (mut automover, transform, homing) in homers.iter_mut() {
+        let to_santa = santa_position - transform.translation.truncate();
+        if to_santa == Vec2::ZERO {
+            continue;
+        }
+        let max_turn = homing.turn_rate * time.delta_secs();
+        let turn = automover.0.angle_to(to_santa).clamp(-max_turn, max_turn);
+        automover.0 = Vec2::from_angle(turn).rotate(automover.0);
+    }
+}
+
+// Bounce automovers off the screen
+fn bounce_automovers_system(
+    mut automovers: Query<(&mut AutoMoving, &mut Transform, &ColliderCircle)>,
+    bounds: Res<GameBounds>,
+) {
+    let width = bounds.width;
+    let height = bounds.height;
+    for (mut automover, mut transform, collider) in automovers.iter_mut() {
+        // Automovers come in different sizes, the collider covers the scaled sprite
+        let half_size = collider.0;
+
+        // After the window shrinks an automover may end up outside of it, so pull it back in first
+        transform.translation.x = transform.translation.x.min(width - half_size).max(half_size);
+        transform.translation.y = transform.translation.y.min(height - half_size).max(half_size);
+        let x = transform.translation.x;
+        let y = transform.translation.y;
+
+        // Bounce off left or right edge. Only reverse when moving towards the edge,
+        // otherwise an automover touching it would flip back and forth every frame.
+        if (x - half_size <= 0.0 && automover.0.x < 0.0) || (x + half_size >= width && automover.0.x > 0.0) {
+            automover.0.x = -automover.0.x; // Reverse x velocity
+        }
+
+        // Bounce off top or bottom edge
+        if (y - half_size <= 0.0 && automover.0.y < 0.0) || (y + half_size >= height && automover.0.y > 0.0) {
+            automover.0.y = -automover.0.y; // Reverse y velocity
+        }
+    }
+}
+
+
+#[derive(Component, Default)]
+struct Santa;
+impl HasSpritePath for Santa {
+    fn sprite_path() -> &'static str { "santa.png" }
+}
+
+fn initialize_santa(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+) {
+    commands.spawn((
+        RunEntity,
+        Santa,
+        // Santa spawns in the middle of the screen
+        Transform::from_xyz(bounds.width / 2.0, bounds.height / 2.0, 0.0),
+        Sprite::from_image(asset_server.load(Santa::sprite_path())),
+        ColliderCircle(16.),
+        Velocity::default(),
+        Facing::default(),
+        Carrying(0),
+        CarriedPoints(0),
+        MoveTarget(None),
+        Dash::default(),
+        Stamina::default(),
+    ));
+    commands.spawn((
+        RunEntity,
+        MoveTargetMarker,
+        Sprite::from_color(MOVE_TARGET_MARKER_COLOR, Vec2::splat(6.0)),
+        Transform::default(),
+        Visibility::Hidden,
+    ));
+}
+
+// Santa can only hold this many presents before he has to deliver them
+const MAX_CARRIED_PRESENTS: u32 = 3;
+// Every carried present slows Santa down by this fraction of his speed
+const CARRY_SPEED_PENALTY: f32 = 0.15;
+
+// Presents Santa picked up but has not delivered yet
+#[derive(Component)]
+struct Carrying(u32);
+// What the carried presents will score once delivered
+#[derive(Component)]
+struct CarriedPoints(u32);
+
+fn carrying_speed_factor(carrying: &Carrying) -> f32 {
+    1.0 - CARRY_SPEED_PENALTY * carrying.0 as f32
+}
+
+fn santa_has_room(santa: Query<&Carrying, With<Santa>>) -> bool {
+    santa.get_single().is_ok_and(|carrying| carrying.0 < MAX_CARRIED_PRESENTS)
+}
+
+fn santa_is_carrying(santa: Query<&Carrying, With<Santa>>) -> bool {
+    santa.get_single().is_ok_and(|carrying| carrying.0 > 0)
+}
+
+// Every present is gone and none is left in Santa's hands, while no exit is waiting yet
+fn board_cleared(
+    presents: Query<(), With<Present>>,
+    gold_presents: Query<(), With<GoldPresent>>,
+    exits: Query<(), With<ExitPoint>>,
+    santa: Query<&Carrying, With<Santa>>,
+) -> bool {
+    presents.is_empty() && gold_presents.is_empty() && exits.is_empty() && santa.get_single().is_ok_and(|carrying| carrying.0 == 0)
+}
+
+fn santa_is_invulnerable(santa: Query<(), (With<Santa>, With<Invulnerable>)>) -> bool {
+    !santa.is_empty()
+}
+
+// Grace period after a hit, snowflakes pass through Santa until it runs out
+const INVULNERABLE_SECONDS: f32 = 1.5;
+// How many times a second Santa blinks while invulnerable
+const INVULNERABLE_BLINK_RATE: f32 = 8.0;
+
+#[derive(Component)]
+struct Invulnerable(Timer);
+
+fn invulnerability_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut santa: Query<(Entity, &mut Invulnerable, &mut Sprite), With<Santa>>,
+) {
+    for (entity, mut invulnerable, mut sprite) in santa.iter_mut() {
+        invulnerable.0.tick(time.delta());
+        if invulnerable.0.finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+            sprite.color.set_alpha(1.0);
+        } else {
+            let blink_on = ((invulnerable.0.elapsed_secs() * INVULNERABLE_BLINK_RATE) as u32).is_multiple_of(2);
+            sprite.color.set_alpha(if blink_on { 0.3 } else { 1.0 });
+        }
+    }
+}
+
+// Where Santa delivers the presents he carries
+#[derive(Component, Default)]
+struct Sleigh;
+impl HasSpritePath for Sleigh {
+    fn sprite_path() -> &'static str { "sleigh.png" }
+}
+
+fn initialize_sleigh(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+) {
+    commands.spawn((
+        RunEntity,
+        Sleigh,
+        // Parked in the bottom right corner
+        Transform::from_xyz(bounds.width - 48.0, 48.0, 0.0),
+        Sprite::from_image(asset_server.load(Sleigh::sprite_path())),
+        ColliderCircle(24.),
+    ));
+}
+
+// Santa has his own speed, so speeding up the automovers does not make him faster too.
+#[derive(Resource)]
+struct SantaSpeed(f32);
+
+// How quickly Santa speeds up towards the input direction, in pixels per second squared
+#[derive(Resource)]
+struct Acceleration(f32);
+// How quickly Santa slows down when there is no input, in pixels per second squared
+#[derive(Resource)]
+struct Friction(f32);
+
+#[derive(Component, Default)]
+struct Velocity(Vec2);
+
+// Stick deflection below this is ignored, so a worn stick does not make Santa drift
+#[derive(Resource)]
+struct GamepadDeadzone(f32);
+
+impl Default for GamepadDeadzone {
+    fn default() -> Self {
+        Self(0.15)
+    }
+}
+
+// Left stick and D-pad of every connected pad. The stick is rescaled past the deadzone, so partial tilts move slower.
+fn gamepad_direction(gamepads: &Query<&Gamepad>, deadzone: f32) -> Vec2 {
+    gamepads
+        .iter()
+        .map(|gamepad| {
+            let stick = gamepad.left_stick();
+            let tilt = ((stick.length() - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0);
+            stick.normalize_or_zero() * tilt + gamepad.dpad()
+        })
+        .sum()
+}
+
+// How far a finger has to drag from where it touched down for full speed
+const TOUCH_JOYSTICK_RADIUS: f32 = 60.0;
+const TOUCH_JOYSTICK_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.3);
+
+// The touch that drives the virtual joystick, any other fingers on the screen are ignored
+#[derive(Resource, Default)]
+struct TouchJoystick(Option<u64>);
+
+#[derive(Component)]
+struct TouchJoystickMarker;
+
+fn track_touch_joystick_system(
+    touches: Res<Touches>,
+    mut joystick: ResMut<TouchJoystick>,
+) {
+    if joystick.0.is_some_and(|id| touches.get_pressed(id).is_none()) {
+        joystick.0 = None;
+    }
+    if joystick.0.is_none() {
+        joystick.0 = touches.iter().map(|touch| touch.id()).min();
+    }
+}
+
+// Offset of the tracked touch from where it started, scaled so the joystick radius is full speed
+fn touch_direction(touches: &Touches, joystick: &TouchJoystick) -> Vec2 {
+    let Some(touch) = joystick.0.and_then(|id| touches.get_pressed(id)) else {
+        return Vec2::ZERO;
+    };
+    let offset = touch.position() - touch.start_position();
+    // Window coordinates grow downwards, the world grows upwards
+    Vec2::new(offset.x, -offset.y) / TOUCH_JOYSTICK_RADIUS
+}
+
+fn initialize_touch_joystick(mut commands: Commands) {
+    commands.spawn((
+        RunEntity,
+        TouchJoystickMarker,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(TOUCH_JOYSTICK_RADIUS * 2.0),
+            height: Val::Px(TOUCH_JOYSTICK_RADIUS * 2.0),
+            ..default()
+        },
+        BackgroundColor(TOUCH_JOYSTICK_COLOR),
+        BorderRadius::MAX,
+        Visibility::Hidden,
+    ));
+}
+
+// Shows a circle around where the joystick touch started while the drag lasts
+fn update_touch_joystick_marker_system(
+    touches: Res<Touches>,
+    joystick: Res<TouchJoystick>,
+    mut markers: Query<(&mut Node, &mut Visibility), With<TouchJoystickMarker>>,
+) {
+    let origin = joystick.0.and_then(|id| touches.get_pressed(id)).map(|touch| touch.start_position());
+    for (mut node, mut visibility) in &mut markers {
+        match origin {
+            Some(origin) => {
+                node.left = Val::Px(origin.x - TOUCH_JOYSTICK_RADIUS);
+                node.top = Val::Px(origin.y - TOUCH_JOYSTICK_RADIUS);
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+// Actions held this frame that affect how Santa moves, plus Dash on the frame it is pressed.
+// Read from the keyboard, or from a recorded trace while one is played back.
+#[derive(Resource, Default)]
+struct MovementKeys(Vec<InputAction>);
+
+impl MovementKeys {
+    const ACTIONS: [InputAction; 5] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Sprint,
+    ];
+
+    fn pressed(&self, action: InputAction) -> bool {
+        self.0.contains(&action)
+    }
+
+    fn direction(&self) -> Vec2 {
+        self.0.iter().map(|action| action.direction()).sum()
+    }
+}
+
+fn read_movement_keys_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    input_bindings: Res<InputBindings>,
+    mut movement_keys: ResMut<MovementKeys>,
+) {
+    movement_keys.0 = MovementKeys::ACTIONS
+        .into_iter()
+        .filter(|action| input_bindings.pressed(*action, &keys))
+        .collect();
+    if input_bindings.just_pressed(InputAction::Dash, &keys) {
+        movement_keys.0.push(InputAction::Dash);
+    }
+}
+
+const INPUT_TRACE_FILE: &str = "input_trace.ron";
+
+// One frame of recorded movement input. The cursor and the clicked target are what Santa steers to without keys.
+#[derive(Serialize, Deserialize)]
+struct InputFrame {
+    delta: f32,
+    actions: Vec<InputAction>,
+    #[serde(default)]
+    cursor: Option<Vec2>,
+    #[serde(default)]
+    move_target: Option<Vec2>,
+}
+
+// Collects the movement input of every played frame, written to `INPUT_TRACE_FILE` on exit.
+// Started with `--record-input` or toggled with F9.
+#[derive(Resource, Default)]
+struct InputRecorder {
+    recording: bool,
+    frames: Vec<InputFrame>,
+}
+
+// Feeds a recorded trace back into the movement instead of the keyboard, started with `--replay-input <file>`
+#[derive(Resource)]
+struct InputPlayback {
+    frames: Vec<InputFrame>,
+    next: usize,
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+fn load_input_playback(path: &str) -> Option<InputPlayback> {
+    let loaded = std::fs::read_to_string(path)
+        .map_err(|error| error.to_string())
+        .and_then(|content| bevy::asset::ron::from_str(&content).map_err(|error| error.to_string()));
+    match loaded {
+        Ok(frames) => Some(InputPlayback { frames, next: 0 }),
+        Err(error) => {
+            eprintln!("Could not load the input trace {}: {}", path, error);
+            None
+        }
+    }
+}
+
+fn recording_input(recorder: Res<InputRecorder>) -> bool {
+    recorder.recording
+}
+
+fn toggle_input_recording_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut recorder: ResMut<InputRecorder>,
+) {
+    if keys.just_pressed(KeyCode::F9) {
+        recorder.recording = !recorder.recording;
+        info!("Input recording {}", if recorder.recording { "started" } else { "stopped" });
+    }
+}
+
+fn record_input_system(
+    time: Res<Time>,
+    movement_keys: Res<MovementKeys>,
+    cursor_world_position: Res<CursorWorldPosition>,
+    santa: Query<&MoveTarget, With<Santa>>,
+    mut recorder: ResMut<InputRecorder>,
+) {
+    recorder.frames.push(InputFrame {
+        delta: time.delta_secs(),
+        actions: movement_keys.0.clone(),
+        cursor: cursor_world_position.0,
+        move_target: santa.single().0,
+    });
+}
+
+// Replays one recorded frame. The recorded delta of the following frame is handed to the clock,
+// so the trace plays back with the same timing it was recorded with.
+fn play_back_input_system(
+    mut commands: Commands,
+    mut playback: ResMut<InputPlayback>,
+    mut movement_keys: ResMut<MovementKeys>,
+    mut cursor_world_position: ResMut<CursorWorldPosition>,
+    mut santa: Query<&mut MoveTarget, With<Santa>>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(frame) = playback.frames.get(playback.next) else {
+        info!("Input playback finished, back to the keyboard");
+        *time_update_strategy = TimeUpdateStrategy::Automatic;
+        commands.remove_resource::<InputPlayback>();
+        return;
+    };
+    movement_keys.0 = frame.actions.clone();
+    cursor_world_position.0 = frame.cursor;
+    santa.single_mut().0 = frame.move_target;
+    playback.next += 1;
+    if let Some(next_frame) = playback.frames.get(playback.next) {
+        *time_update_strategy = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(next_frame.delta));
+    }
+}
+
+fn save_input_trace_system(recorder: Res<InputRecorder>) {
+    if recorder.frames.is_empty() {
+        return;
+    }
+    let trace = match bevy::asset::ron::ser::to_string_pretty(&recorder.frames, default()) {
+        Ok(trace) => trace,
+        Err(error) => {
+            error!("Could not serialize the input trace: {}", error);
+            return;
+        }
+    };
+    match std::fs::write(INPUT_TRACE_FILE, trace) {
+        Ok(()) => info!("Input trace of {} frames saved to {}", recorder.frames.len(), INPUT_TRACE_FILE),
+        Err(error) => error!("Could not save {}: {}", INPUT_TRACE_FILE, error),
+    }
+}
+
+// What the player asks Santa to do this frame, whichever devices it came from
+#[derive(Resource, Default)]
+struct PlayerInput {
+    move_dir: Vec2,
+    sprint: bool,
+    dash: bool,
+}
+
+impl PlayerInput {
+    // The combined direction of every device.
+    // Clamped rather than normalized, so two keys held at once are no faster than one but a half tilted stick is still slower.
+    fn direction(&self) -> Vec2 {
+        self.move_dir.clamp_length_max(1.0)
+    }
+}
+
+fn read_keyboard_input_system(
+    movement_keys: Res<MovementKeys>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    *player_input = PlayerInput {
+        move_dir: movement_keys.direction(),
+        sprint: movement_keys.pressed(InputAction::Sprint),
+        dash: movement_keys.pressed(InputAction::Dash),
+    };
+}
+
+fn read_gamepad_input_system(
+    gamepads: Query<&Gamepad>,
+    deadzone: Res<GamepadDeadzone>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    player_input.move_dir += gamepad_direction(&gamepads, deadzone.0);
+    player_input.dash |= gamepad_just_pressed(&gamepads, GamepadButton::East);
+}
+
+fn read_touch_input_system(
+    touches: Res<Touches>,
+    touch_joystick: Res<TouchJoystick>,
+    mut player_input: ResMut<PlayerInput>,
+) {
+    player_input.move_dir += touch_direction(&touches, &touch_joystick);
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn move_santa_system(
+    player_input: Res<PlayerInput>,
+    follow_cursor: Res<FollowCursor>,
+    cursor_world_position: Res<CursorWorldPosition>,
+    time: Res<Time>,
+    speed: Res<SantaSpeed>,
+    acceleration: Res<Acceleration>,
+    friction: Res<Friction>,
+    mut santa: Query<(&Transform, &mut Velocity, &mut Facing, &mut Sprite, &Carrying, &mut MoveTarget, &mut Stamina, Option<&SpeedModifier>), With<Santa>>,
+    ice_patches: Query<(&Transform, &IcePatch), Without<Santa>>,
+) {
+    let (transform, mut velocity, mut facing, mut sprite, carrying, mut move_target, mut stamina, speed_modifier) = santa.single_mut();
+
+    let mut direction = player_input.direction();
+    if direction != Vec2::ZERO {
+        // Keyboard and gamepad always override a clicked target
+        move_target.0 = None;
+    } else if follow_cursor.0 {
+        direction = follow_cursor_direction(transform.translation.truncate(), cursor_world_position.0);
+    } else if let Some(target) = move_target.0 {
+        let to_target = target - transform.translation.truncate();
+        if to_target.length() <= MOVE_TARGET_ARRIVAL_DISTANCE {
+            move_target.0 = None;
+        } else {
+            direction = to_target.normalize();
+        }
+    }
+    face_towards(&mut facing, &mut sprite, direction);
+    let wants_sprint = direction != Vec2::ZERO && player_input.sprint;
+    let sprint_factor = if stamina.sprint(wants_sprint, time.delta_secs()) { SPRINT_SPEED_FACTOR } else { 1.0 };
+    let boost_factor = speed_modifier.map_or(1.0, |modifier| modifier.factor);
+    let max_speed = speed.0 * carrying_speed_factor(carrying) * sprint_factor * boost_factor;
+    let santa_position = transform.translation.truncate();
+    let on_ice = ice_patches.iter().any(|(patch_transform, patch)| patch.contains(patch_transform.translation.truncate(), santa_position));
+    let (acceleration, friction) = if on_ice {
+        (acceleration.0 * ICE_ACCELERATION_FACTOR, friction.0 * ICE_FRICTION_FACTOR)
+    } else {
+        (acceleration.0, friction.0)
+    };
+    steer_santa(&mut velocity, direction, max_speed, acceleration, friction, time.delta_secs());
+}
+
+const SPEED_BOOST_FACTOR: f32 = 1.5;
+const SPEED_BOOST_SECONDS: f32 = 4.0;
+
+// Temporarily scales Santa's own speed, the automovers are not affected
+#[derive(Component)]
+struct SpeedModifier {
+    factor: f32,
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct UiBoostIndicator;
+
+// Inserting replaces any active boost, so a second pickup refreshes the timer instead of compounding the factor
+fn grant_speed_boost_system(
+    mut commands: Commands,
+    santa: Query<Entity, With<Santa>>,
+) {
+    commands.entity(santa.single()).insert(SpeedModifier {
+        factor: SPEED_BOOST_FACTOR,
+        timer: Timer::from_seconds(SPEED_BOOST_SECONDS, TimerMode::Once),
+    });
+}
+
+fn tick_speed_modifier_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut santa: Query<(Entity, &mut SpeedModifier)>,
+) {
+    for (entity, mut speed_modifier) in santa.iter_mut() {
+        if speed_modifier.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<SpeedModifier>();
+        }
+    }
+}
+
+fn update_boost_ui(
+    santa: Query<Has<SpeedModifier>, With<Santa>>,
+    mut indicators: Query<&mut Visibility, With<UiBoostIndicator>>,
+) {
+    let boosted = santa.single();
+    for mut visibility in indicators.iter_mut() {
+        *visibility = if boosted { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+const SPRINT_SPEED_FACTOR: f32 = 1.6;
+// A full stamina bar lasts this long when sprinting
+const SPRINT_SECONDS: f32 = 2.0;
+// Refilling an empty bar takes this long
+const STAMINA_REGEN_SECONDS: f32 = 4.0;
+// Once drained, sprinting is blocked until stamina is back above this fraction, so it cannot stutter at zero
+const STAMINA_RECOVERY_FRACTION: f32 = 0.25;
+
+#[derive(Component)]
+struct Stamina {
+    current: f32,
+    max: f32,
+    exhausted: bool,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self { current: SPRINT_SECONDS, max: SPRINT_SECONDS, exhausted: false }
+    }
+}
+
+impl Stamina {
+    // Drains stamina while sprinting and regenerates it otherwise, returns whether Santa actually sprints
+    fn sprint(&mut self, wants_sprint: bool, delta: f32) -> bool {
+        if self.exhausted && self.current >= self.max * STAMINA_RECOVERY_FRACTION {
+            self.exhausted = false;
+        }
+        let sprinting = wants_sprint && !self.exhausted;
+        if sprinting {
+            self.current = (self.current - delta).max(0.0);
+            self.exhausted = self.current == 0.0;
+        } else {
+            self.current = (self.current + self.max / STAMINA_REGEN_SECONDS * delta).min(self.max);
+        }
+        sprinting
+    }
+}
+
+#[derive(Component)]
+struct UiStaminaBar;
+
+fn update_stamina_ui(
+    santa: Query<&Stamina, With<Santa>>,
+    mut bars: Query<(&mut Node, &mut BackgroundColor), With<UiStaminaBar>>,
+) {
+    let stamina = santa.single();
+    for (mut node, mut color) in bars.iter_mut() {
+        node.width = Val::Percent(stamina.current / stamina.max * 100.0);
+        // Greyed out while exhausted, to show why sprinting does nothing
+        color.0 = if stamina.exhausted { Color::srgb(0.5, 0.5, 0.5) } else { Color::srgb(0.4, 1.0, 0.4) };
+    }
+}
+
+// A dash covers this distance in pixels over its duration
+const DASH_DISTANCE: f32 = 150.0;
+const DASH_SECONDS: f32 = 0.15;
+const DASH_COOLDOWN_SECONDS: f32 = 2.0;
+
+// While a dash is active Santa moves in its direction and ignores input
+#[derive(Component)]
+struct Dash {
+    cooldown: Timer,
+    active: Option<(Vec2, Timer)>,
+}
+
+impl Default for Dash {
+    fn default() -> Self {
+        // Santa starts every run with the dash ready
+        let mut cooldown = Timer::from_seconds(DASH_COOLDOWN_SECONDS, TimerMode::Once);
+        cooldown.set_elapsed(cooldown.duration());
+        Self { cooldown, active: None }
+    }
+}
+
+#[derive(Component)]
+struct UiDashBar;
+
+fn santa_is_dashing(santa: Query<&Dash, With<Santa>>) -> bool {
+    santa.get_single().is_ok_and(|dash| dash.active.is_some())
+}
+
+fn dash_system(
+    player_input: Res<PlayerInput>,
+    time: Res<Time>,
+    mut santa: Query<(&mut Dash, &mut Velocity, &Facing), With<Santa>>,
+) {
+    let (mut dash, mut velocity, facing) = santa.single_mut();
+    dash.cooldown.tick(time.delta());
+    if let Some((direction, timer)) = dash.active.as_mut() {
+        timer.tick(time.delta());
+        velocity.0 = *direction * DASH_DISTANCE / DASH_SECONDS;
+        if timer.finished() {
+            dash.active = None;
+        }
+        return;
+    }
+    if !player_input.dash || !dash.cooldown.finished() {
+        return;
+    }
+    // Without a held direction Santa dashes the way he is looking
+    let direction = match player_input.move_dir.try_normalize() {
+        Some(direction) => direction,
+        None if *facing == Facing::Left => Vec2::NEG_X,
+        None => Vec2::X,
+    };
+    velocity.0 = direction * DASH_DISTANCE / DASH_SECONDS;
+    dash.active = Some((direction, Timer::from_seconds(DASH_SECONDS, TimerMode::Once)));
+    dash.cooldown.reset();
+}
+
+fn update_dash_ui(
+    santa: Query<&Dash, With<Santa>>,
+    mut bars: Query<&mut Node, With<UiDashBar>>,
+) {
+    let dash = santa.single();
+    for mut node in bars.iter_mut() {
+        node.width = Val::Percent(dash.cooldown.fraction() * 100.0);
+    }
+}
+
+// Santa is close enough to a clicked target to stop walking towards it
+const MOVE_TARGET_ARRIVAL_DISTANCE: f32 = 4.0;
+const MOVE_TARGET_MARKER_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.6);
+
+// Where Santa walks on his own after a left click, if anywhere
+#[derive(Component)]
+struct MoveTarget(Option<Vec2>);
+
+#[derive(Component)]
+struct MoveTargetMarker;
+
+// The cursor in world coordinates, `None` while it is outside of the window
+#[derive(Resource, Default)]
+struct CursorWorldPosition(Option<Vec2>);
+
+fn track_cursor_system(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut cursor_world_position: ResMut<CursorWorldPosition>,
+) {
+    let (Ok(primary_window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    // Goes through the camera, so it stays right however the camera is placed
+    cursor_world_position.0 = primary_window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok());
+}
+
+// Santa stops this close to the cursor when following it, so he does not jitter around it
+const FOLLOW_CURSOR_STOP_DISTANCE: f32 = 2.0;
+
+// When on, Santa steers towards the cursor every frame. Toggled with F and kept between runs.
+#[derive(Resource, Default)]
+struct FollowCursor(bool);
+
+#[derive(Component)]
+struct UiFollowCursorText;
+
+fn toggle_follow_cursor_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut follow_cursor: ResMut<FollowCursor>,
+) {
+    if keys.just_pressed(KeyCode::KeyF) {
+        follow_cursor.0 = !follow_cursor.0;
+    }
+}
+
+fn update_follow_cursor_ui(
+    follow_cursor: Res<FollowCursor>,
+    mut texts: Query<&mut Visibility, With<UiFollowCursorText>>,
+) {
+    for mut visibility in texts.iter_mut() {
+        *visibility = if follow_cursor.0 { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+// Direction towards the cursor with full throttle, nothing once Santa is there or the cursor left the window
+fn follow_cursor_direction(santa_position: Vec2, cursor: Option<Vec2>) -> Vec2 {
+    cursor
+        .map(|cursor| cursor - santa_position)
+        .filter(|offset| offset.length() > FOLLOW_CURSOR_STOP_DISTANCE)
+        .map_or(Vec2::ZERO, Vec2::normalize)
+}
+
+fn set_move_target_system(
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    bounds: Res<GameBounds>,
+    cursor_world_position: Res<CursorWorldPosition>,
+    mut santa: Query<&mut MoveTarget, With<Santa>>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(position) = cursor_world_position.0 else {
+        return;
+    };
+    // Santa is kept within the screen, so keep the target where he can actually stand
+    let half_size = 32. / 2.;
+    let target = position.clamp(Vec2::splat(half_size), bounds.size() - half_size);
+    santa.single_mut().0 = Some(target);
+}
+
+fn update_move_target_marker_system(
+    santa: Query<&MoveTarget, With<Santa>>,
+    mut markers: Query<(&mut Transform, &mut Visibility), With<MoveTargetMarker>>,
+) {
+    let move_target = santa.single();
+    for (mut transform, mut visibility) in &mut markers {
+        match move_target.0 {
+            Some(target) => {
+                transform.translation = target.extend(-1.0);
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+// The way Santa looks, kept while standing still
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq)]
+enum Facing {
+    Left,
+    #[default]
+    Right,
+}
+
+// Turns Santa to the horizontal direction he is heading, the sprite itself faces right
+fn face_towards(facing: &mut Facing, sprite: &mut Sprite, direction: Vec2) {
+    if direction.x < 0.0 {
+        *facing = Facing::Left;
+    } else if direction.x > 0.0 {
+        *facing = Facing::Right;
+    }
+    sprite.flip_x = *facing == Facing::Left;
+}
+
+// On ice Santa picks up speed slowly and keeps sliding long after the keys are released
+const ICE_ACCELERATION_FACTOR: f32 = 0.35;
+const ICE_FRICTION_FACTOR: f32 = 0.08;
+const ICE_PATCH_MIN_RADIUS: f32 = 50.0;
+const ICE_PATCH_MAX_RADIUS: f32 = 80.0;
+// Patches keep this far from every window edge, so a free corridor always runs around the screen
+const ICE_EDGE_CORRIDOR: f32 = 48.0;
+// Gives up on a patch that does not fit, small windows may only have room for fewer of them
+const ICE_PATCH_PLACEMENT_ATTEMPTS: u32 = 50;
+
+// A static slippery area, only Santa's movement cares about it
+#[derive(Component)]
+struct IcePatch {
+    radius: f32,
+}
+
+impl IcePatch {
+    fn contains(&self, center: Vec2, position: Vec2) -> bool {
+        center.distance(position) < self.radius
+    }
+}
+
+fn initialize_ice_patches(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+) {
+    let count = rng.0.u32(2..=3);
+    for _ in 0..count {
+        let radius = ICE_PATCH_MIN_RADIUS + rng.0.f32() * (ICE_PATCH_MAX_RADIUS - ICE_PATCH_MIN_RADIUS);
+        let Some(position) = ice_patch_position(&mut rng.0, bounds.size(), &free_zone, radius) else {
+            continue;
+        };
+        commands.spawn((
+            RunEntity,
+            IcePatch { radius },
+            // Below everything else
+            Transform::from_translation(position.extend(-1.0)),
+            Sprite {
+                image: asset_server.load("ice.png"),
+                custom_size: Some(Vec2::splat(radius * 2.0)),
+                ..default()
+            },
+        ));
+    }
+}
+
+// Somewhere fully outside of the FREE_ZONE and clear of the edge corridor, if there is room for it
+fn ice_patch_position(rng: &mut fastrand::Rng, size: Vec2, free_zone: &FreeZone, radius: f32) -> Option<Vec2> {
+    let margin = ICE_EDGE_CORRIDOR + radius;
+    if size.x <= 2.0 * margin || size.y <= 2.0 * margin {
+        return None;
+    }
+    let center = size / 2.0;
+    let free_zone = free_zone.radius(size);
+    (0..ICE_PATCH_PLACEMENT_ATTEMPTS).find_map(|_| {
+        let x = margin + rng.f32() * (size.x - 2.0 * margin);
+        let y = margin + rng.f32() * (size.y - 2.0 * margin);
+        let position = Vec2::new(x, y);
+        (position.distance(center) > free_zone + radius).then_some(position)
+    })
+}
+
+const TREE_RADIUS: f32 = 24.0;
+const TREE_MIN_COUNT: u32 = 3;
+const TREE_MAX_COUNT: u32 = 5;
+// Santa's width, kept free between a tree and anything else so trees never wall off a path
+const TREE_GAP: f32 = 32.0;
+const TREE_PLACEMENT_ATTEMPTS: u32 = 50;
+
+// A solid obstacle, Santa walks around it and automovers bounce off it
+#[derive(Component)]
+struct Tree;
+
+fn initialize_trees(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    // The presents, the sleigh and the candy canes are already in place
+    colliders: Query<(&Transform, &ColliderCircle)>,
+) {
+    let mut taken: Vec<(Vec2, f32)> = colliders.iter().map(|(transform, collider)| (transform.translation.truncate(), collider.0)).collect();
+    let count = rng.0.u32(TREE_MIN_COUNT..=TREE_MAX_COUNT);
+    for _ in 0..count {
+        let Some(position) = tree_position(&mut rng.0, bounds.size(), &free_zone, &taken) else {
+            continue;
+        };
+        taken.push((position, TREE_RADIUS));
+        commands.spawn((
+            RunEntity,
+            Tree,
+            Transform::from_translation(position.extend(0.0)),
+            Sprite::from_image(asset_server.load("tree.png")),
+            ColliderCircle(TREE_RADIUS),
+        ));
+    }
+}
+
+// Somewhere outside of the FreeZone and at least a gap away from the edges and everything already taken
+fn tree_position(rng: &mut fastrand::Rng, size: Vec2, free_zone: &FreeZone, taken: &[(Vec2, f32)]) -> Option<Vec2> {
+    let margin = TREE_GAP + TREE_RADIUS;
+    if size.x <= 2.0 * margin || size.y <= 2.0 * margin {
+        return None;
+    }
+    let center = size / 2.0;
+    let free_zone = free_zone.radius(size);
+    (0..TREE_PLACEMENT_ATTEMPTS).find_map(|_| {
+        let x = margin + rng.f32() * (size.x - 2.0 * margin);
+        let y = margin + rng.f32() * (size.y - 2.0 * margin);
+        let position = Vec2::new(x, y);
+        let clear = position.distance(center) > free_zone + TREE_RADIUS
+            && taken.iter().all(|&(other, radius)| position.distance(other) > TREE_RADIUS + radius + TREE_GAP);
+        clear.then_some(position)
+    })
+}
+
+// Where a circle overlapping the tree has to move to just touch it, and the normal pointing away from the tree
+fn push_out_of_tree(tree: Vec2, position: Vec2, radius: f32) -> Option<(Vec2, Vec2)> {
+    let reach = TREE_RADIUS + radius;
+    let offset = position - tree;
+    if offset.length_squared() >= reach * reach {
+        return None;
+    }
+    // Dead center has no way out, any direction will do
+    let normal = offset.try_normalize().unwrap_or(Vec2::Y);
+    Some((tree + normal * reach, normal))
+}
+
+// Runs after Santa moved, so he stops at the bark and slides along it instead of passing through
+fn santa_tree_collision_system(
+    trees: Query<&Transform, (With<Tree>, Without<Santa>)>,
+    mut santa: Query<(&mut Transform, &mut Velocity, &ColliderCircle), With<Santa>>,
+) {
+    let Ok((mut santa_transform, mut velocity, collider)) = santa.get_single_mut() else {
+        return;
+    };
+    for tree_transform in trees.iter() {
+        let Some((position, normal)) = push_out_of_tree(tree_transform.translation.truncate(), santa_transform.translation.truncate(), collider.0) else {
+            continue;
+        };
+        santa_transform.translation = position.extend(santa_transform.translation.z);
+        // Only the part of the velocity heading into the tree is lost
+        let into_tree = velocity.0.dot(normal);
+        if into_tree < 0.0 {
+            velocity.0 -= normal * into_tree;
+        }
+    }
+}
+
+// Bounce automovers off trees the same way they bounce off the screen edges
+fn bounce_off_trees_system(
+    trees: Query<&Transform, (With<Tree>, Without<AutoMoving>)>,
+    mut automovers: Query<(&mut AutoMoving, &mut Transform, &ColliderCircle)>,
+) {
+    for tree_transform in trees.iter() {
+        let tree = tree_transform.translation.truncate();
+        for (mut automover, mut transform, collider) in automovers.iter_mut() {
+            let Some((position, normal)) = push_out_of_tree(tree, transform.translation.truncate(), collider.0) else {
+                continue;
+            };
+            transform.translation = position.extend(transform.translation.z);
+            // Only reflect when heading into the tree, like at the edges
+            if automover.0.dot(normal) < 0.0 {
+                automover.0 = automover.0.reflect(normal);
+            }
+        }
+    }
+}
+
+// Accelerates Santa towards the given direction, whoever decided on it, or lets him slow down without one.
+// The length of the direction is the throttle and should be at most 1, so a shorter one moves Santa slower.
+fn steer_santa(velocity: &mut Velocity, direction: Vec2, max_speed: f32, acceleration: f32, friction: f32, delta: f32) {
+    let (target, rate) = if direction == Vec2::ZERO {
+        (Vec2::ZERO, friction)
+    } else {
+        (direction * max_speed, acceleration)
+    };
+    let change = target - velocity.0;
+    velocity.0 += change.clamp_length_max(rate * delta);
+    velocity.0 = velocity.0.clamp_length_max(max_speed);
+}
+
+fn apply_velocity_system(
+    time: Res<Time>,
+    mut run_stats: ResMut<RunStats>,
+    bounds: Res<GameBounds>,
+    mut santa: Query<(&mut Transform, &mut Velocity), With<Santa>>,
+) {
+    let (mut santa_transform, mut velocity) = santa.single_mut();
+    let start = santa_transform.translation;
+    let width = bounds.width;
+    let height = bounds.height;
+    let movement = velocity.0 * time.delta_secs();
+
+    // Keep Santa within the screen
+    let half_size = 32. / 2.;
+    santa_transform.translation.x = (santa_transform.translation.x + movement.x).min(width - half_size).max(half_size);
+    santa_transform.translation.y = (santa_transform.translation.y + movement.y).min(height - half_size).max(half_size);
+    // Running into an edge stops Santa along that axis, so he does not stay glued to it
+    if santa_transform.translation.x != start.x + movement.x {
+        velocity.0.x = 0.0;
+    }
+    if santa_transform.translation.y != start.y + movement.y {
+        velocity.0.y = 0.0;
+    }
+    // Measured from the actual position change, which is less than the velocity when Santa runs into an edge
+    run_stats.distance_travelled += santa_transform.translation.distance(start);
+}
+
+// What Santa ran into. Adding a new collidable is a new variant plus a `Collidable` impl for its component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionKind {
+    Present,
+    GoldPresent,
+    Snowflake,
+    Sleigh,
+    ExitPoint,
+    HeartPickup,
+    ShieldPickup,
+    SlowMoPickup,
+    FreezePickup,
+    MagnetPickup,
+    BoostPickup,
+    BombPickup,
+    CandyCane,
+    ReindeerPickup,
+    Snowman,
+    SleighHazard,
+}
+
+impl CollisionKind {
+    fn is_present(self) -> bool {
+        matches!(self, CollisionKind::Present | CollisionKind::GoldPresent)
+    }
+
+    // Lives lost when Santa runs into it
+    fn damage(self) -> Option<u32> {
+        match self {
+            CollisionKind::Snowflake => Some(1),
+            CollisionKind::Snowman => Some(SNOWMAN_DAMAGE),
+            CollisionKind::SleighHazard => Some(1),
+            _ => None,
+        }
+    }
+
+    // The sleigh stays parked for the whole run, reaching the exit ends it and the snowman has to be beaten,
+    // everything else is used up
+    fn is_consumed(self) -> bool {
+        match self {
+            CollisionKind::Present | CollisionKind::GoldPresent | CollisionKind::Snowflake | CollisionKind::HeartPickup | CollisionKind::ShieldPickup
+                | CollisionKind::SlowMoPickup | CollisionKind::FreezePickup | CollisionKind::MagnetPickup
+                | CollisionKind::BoostPickup | CollisionKind::BombPickup | CollisionKind::CandyCane
+                | CollisionKind::ReindeerPickup => true,
+            CollisionKind::Sleigh | CollisionKind::ExitPoint | CollisionKind::Snowman | CollisionKind::SleighHazard => false,
+        }
+    }
+}
+
+// Marks an object whose collision was already reported, so it cannot fire again before it is despawned
+#[derive(Component)]
+struct Collided;
+
+// Trait for generic systems that need to know which kind of collision a component produces
+trait Collidable {
+    const KIND: CollisionKind;
+}
+impl Collidable for Present {
+    const KIND: CollisionKind = CollisionKind::Present;
+}
+impl Collidable for GoldPresent {
+    const KIND: CollisionKind = CollisionKind::GoldPresent;
+}
+impl Collidable for Snowflake {
+    const KIND: CollisionKind = CollisionKind::Snowflake;
+}
+impl Collidable for Sleigh {
+    const KIND: CollisionKind = CollisionKind::Sleigh;
+}
+impl Collidable for ExitPoint {
+    const KIND: CollisionKind = CollisionKind::ExitPoint;
+}
+impl Collidable for HeartPickup {
+    const KIND: CollisionKind = CollisionKind::HeartPickup;
+}
+impl Collidable for ShieldPickup {
+    const KIND: CollisionKind = CollisionKind::ShieldPickup;
+}
+impl Collidable for SlowMoPickup {
+    const KIND: CollisionKind = CollisionKind::SlowMoPickup;
+}
+impl Collidable for FreezePickup {
+    const KIND: CollisionKind = CollisionKind::FreezePickup;
+}
+impl Collidable for MagnetPickup {
+    const KIND: CollisionKind = CollisionKind::MagnetPickup;
+}
+impl Collidable for BoostPickup {
+    const KIND: CollisionKind = CollisionKind::BoostPickup;
+}
+impl Collidable for BombPickup {
+    const KIND: CollisionKind = CollisionKind::BombPickup;
+}
+impl Collidable for CandyCane {
+    const KIND: CollisionKind = CollisionKind::CandyCane;
+}
+impl Collidable for ReindeerPickup {
+    const KIND: CollisionKind = CollisionKind::ReindeerPickup;
+}
+impl Collidable for Snowman {
+    const KIND: CollisionKind = CollisionKind::Snowman;
+}
+impl Collidable for SleighHazard {
+    const KIND: CollisionKind = CollisionKind::SleighHazard;
+}
+
+#[derive(Event)]
+pub struct CollisionEvent {
+    entity: Entity,
+    kind: CollisionKind,
+    // Where the object was when Santa touched it, it may be gone by the time the event is read
+    position: Vec2,
+}
+
+// Run condition for systems that only care about one kind of collision
+fn on_collision(kind: CollisionKind) -> impl FnMut(EventReader<CollisionEvent>) -> bool + Clone {
+    move |mut event_reader: EventReader<CollisionEvent>| event_reader.read().any(|event| event.kind == kind)
+}
+
+// Run condition for systems reacting to any kind of present being collected
+fn on_present_collision(mut event_reader: EventReader<CollisionEvent>) -> bool {
+    event_reader.read().any(|event| event.kind.is_present())
+}
+
+// Scales the sum of collider radii. Values above 1.0 make collisions fire before the sprites actually touch.
+#[derive(Resource)]
+struct CollisionFudge(f32);
+
+// Two circles overlap when the distance between their centers is smaller than the sum of their radii
+fn circles_overlap(a: Vec2, a_radius: f32, b: Vec2, b_radius: f32, fudge: f32) -> bool {
+    a.distance(b) < (a_radius + b_radius) * fudge
+}
+
+// Side of a spatial grid cell, a bit larger than the biggest sprites
+const SPATIAL_GRID_CELL_SIZE: f32 = 64.0;
+
+// Uniform spatial hash of every collider except Santa, so collision detection only looks at the cells around him
+#[derive(Resource, Default)]
+struct SpatialGrid {
+    cells: HashMap<IVec2, Vec<Entity>>,
+    // Largest collider in the grid, it decides how many cells around Santa have to be checked
+    max_radius: f32,
+}
+
+impl SpatialGrid {
+    fn cell(position: Vec2) -> IVec2 {
+        (position / SPATIAL_GRID_CELL_SIZE).floor().as_ivec2()
+    }
+
+    fn rebuild(&mut self, colliders: impl Iterator<Item = (Entity, Vec2, f32)>) {
+        // Keep the cell vectors around to avoid reallocating them every frame
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+        self.max_radius = 0.0;
+        for (entity, position, radius) in colliders {
+            self.cells.entry(Self::cell(position)).or_default().push(entity);
+            self.max_radius = self.max_radius.max(radius);
+        }
+    }
+
+    // Entities that may be within `reach` of `position`
+    fn entities_near(&self, position: Vec2, reach: f32) -> impl Iterator<Item = Entity> + '_ {
+        let min = Self::cell(position - Vec2::splat(reach));
+        let max = Self::cell(position + Vec2::splat(reach));
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn build_spatial_grid_system(
+    mut grid: ResMut<SpatialGrid>,
+    colliders: Query<(Entity, &Transform, &ColliderCircle), (Without<Santa>, Without<Reindeer>, Without<Tree>)>,
+) {
+    grid.rebuild(colliders.iter().map(|(entity, transform, collider)| (entity, transform.translation.truncate(), collider.0)));
+}
+
+// Whether automovers bounce off each other. Can be turned off to save time on slow machines.
+#[derive(Resource, PartialEq)]
+struct AutomoverCollisions(bool);
+
+// Bounces overlapping automovers apart like equal billiard balls, swapping the parts of their directions along the hit
+fn automover_collision_system(
+    grid: Res<SpatialGrid>,
+    mut automovers: Query<(Entity, &Transform, &ColliderCircle, &mut AutoMoving)>,
+) {
+    let mut hits = Vec::new();
+    for (entity, transform, collider, automover) in automovers.iter() {
+        let position = transform.translation.truncate();
+        for other in grid.entities_near(position, collider.0 + grid.max_radius) {
+            // Every pair is seen from both sides, only handle it once
+            if other <= entity {
+                continue;
+            }
+            let Ok((_, other_transform, other_collider, other_automover)) = automovers.get(other) else {
+                continue;
+            };
+            let other_position = other_transform.translation.truncate();
+            if !circles_overlap(position, collider.0, other_position, other_collider.0, 1.0) {
+                continue;
+            }
+            let Some(normal) = (other_position - position).try_normalize() else {
+                continue;
+            };
+            // Moving apart already, bouncing again would pull them back together
+            let closing = automover.0.dot(normal) - other_automover.0.dot(normal);
+            if closing > 0.0 {
+                hits.push((entity, other, normal * closing));
+            }
+        }
+    }
+    for (entity, other, exchange) in hits {
+        if let Ok([(_, _, _, mut automover), (_, _, _, mut other_automover)]) = automovers.get_many_mut([entity, other]) {
+            // Directions stay unit length, the speed is shared by all automovers
+            automover.0 = (automover.0 - exchange).normalize_or(automover.0);
+            other_automover.0 = (other_automover.0 + exchange).normalize_or(other_automover.0);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn detect_collisions_system<C: Component + Collidable>(
+    mut commands: Commands,
+    mut event_writer: EventWriter<CollisionEvent>,
+    fudge: Res<CollisionFudge>,
+    grid: Res<SpatialGrid>,
+    objects: Query<(&Transform, &ColliderCircle), (With<C>, Without<Collided>)>,
+    santa: Query<(&Transform, &ColliderCircle), With<Santa>>,
+) {
+    let (santa_transform, santa_collider) = santa.single();
+    let santa_position = santa_transform.translation.truncate();
+    let reach = (santa_collider.0 + grid.max_radius) * fudge.0;
+    for entity in grid.entities_near(santa_position, reach) {
+        // The grid holds every collider, only the ones of this kind matter here
+        let Ok((object_transform, object_collider)) = objects.get(entity) else {
+            continue;
+        };
+        let object_position = object_transform.translation.truncate();
+        if circles_overlap(object_position, object_collider.0, santa_position, santa_collider.0, fudge.0) {
+            event_writer.send(CollisionEvent { entity, kind: C::KIND, position: object_position });
+            if C::KIND.is_consumed() {
+                commands.entity(entity).insert(Collided);
+            }
+        }
+    }
+}
+
+fn remove_entity_on_collission_system(
+    mut commands: Commands,
+    mut event_reader: EventReader<CollisionEvent>,
+) {
+    for event in event_reader.read().filter(|event| event.kind.is_consumed()) {
+        commands.entity(event.entity).despawn_recursive();
+    }
+}
+
+#[derive(Resource)]
+struct Score(u32);
+#[derive(Resource)]
+struct Lives(u32);
+// Hearts Santa begins every run with, follows the selected difficulty
+#[derive(Resource)]
+struct StartingLives(u32);
+
+fn sync_starting_lives_system(
+    difficulty: Res<Difficulty>,
+    mut starting_lives: ResMut<StartingLives>,
+) {
+    starting_lives.0 = difficulty.lives();
+}
+
+fn sync_free_zone_system(
+    difficulty: Res<Difficulty>,
+    mut free_zone: ResMut<FreeZone>,
+) {
+    free_zone.0 = difficulty.free_zone();
+}
+
+// Hearts wrap onto another row past this many, so large counts do not run across the HUD
+const HEARTS_PER_ROW: u32 = 5;
+
+#[derive(Component)]
+struct UiScoreText;
+#[derive(Component)]
+struct UiCarryingText;
+#[derive(Component)]
+struct UiLevelText;
+#[derive(Component)]
+struct UiHighScoreText;
+#[derive(Component)]
+struct UiTimerText;
+#[derive(Component)]
+struct UiRoundTimerText;
+#[derive(Component)]
+struct UiHeart(u32);
+// The row holding the hearts, so hearts given back can be added to it
+#[derive(Component)]
+struct UiHearts;
+
+// Loaded once up front, so the lives UI does not need the asset server to add hearts back
+#[derive(Resource)]
+struct UiImages {
+    heart: Handle<Image>,
+    shield: Handle<Image>,
+    boost: Handle<Image>,
+    arrow: Handle<Image>,
+}
+
+fn load_ui_images(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(UiImages {
+        heart: asset_server.load("heart.png"),
+        shield: asset_server.load("shield.png"),
+        boost: asset_server.load("boost.png"),
+        arrow: asset_server.load("arrow.png"),
+    });
+}
+
+fn spawn_heart(parent: &mut ChildBuilder, ui_images: &UiImages, index: u32) {
+    parent.spawn((
+        Node {
+            width: Val::Px(21.0),
+            height: Val::Px(18.0),
+            ..default()
+        },
+        ImageNode::new(ui_images.heart.clone()),
+        UiHeart(index),
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn initialize_ui(
+    mut commands: Commands,
+    ui_images: Res<UiImages>,
+    difficulty: Res<Difficulty>,
+    starting_lives: Res<StartingLives>,
+    high_score: Res<HighScore>,
+    best_score: Res<BestScore>,
+    game_mode: Res<GameMode>,
+    new_game_plus: Res<NewGamePlus>,
+    follow_cursor: Res<FollowCursor>,
+    win_condition: Res<WinCondition>,
+) {
+    // Add score and level labels
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(10.0),
+            column_gap: Val::Px(20.0),
+            ..default()
+        },
+        RunEntity,
+    )).with_children(|parent| {
+        parent.spawn((Text::new(score_label(0, new_game_plus.multiplier)), UiScoreText));
+        parent.spawn((Text::new("Carrying: 0"), UiCarryingText));
+        parent.spawn((Text::new("Level: 1"), UiLevelText));
+        parent.spawn(Text::new(difficulty.name()));
+        parent.spawn((Text::new(format!("High score: {}", high_score.0)), UiHighScoreText));
+        if let WinCondition::ReachScore(target) = *win_condition {
+            parent.spawn(Text::new(format!("Target: {}", target)));
+        }
+    });
+    // Add the session best below the score
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(40.0),
+            ..default()
+        },
+        Text::new(format!("Best: {}", best_score.0)),
+        RunEntity,
+    ));
+    // Add elapsed time label below the best score
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(70.0),
+            ..default()
+        },
+        Text::new(format_time(0.0)),
+        UiTimerText,
+        RunEntity,
+    ));
+    // Add the combo multiplier below the timer, hidden until a combo starts
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(100.0),
+            ..default()
+        },
+        Text::new(""),
+        TextColor(Color::srgba(1.0, 0.85, 0.2, 0.0)),
+        UiComboText,
+        RunEntity,
+    ));
+    // Add the next candy cane to grab below the combo
+    if *game_mode != GameMode::Survival {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                top: Val::Px(130.0),
+                ..default()
+            },
+            Text::new("Next candy cane: 1"),
+            UiCandyCaneText,
+            RunEntity,
+        ));
+    }
+    // Add a screen wide tint, only visible while the automovers are slowed down
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.55, 0.35, 0.85, 0.0)),
+        UiSlowMoTint,
+        RunEntity,
+    ));
+    // Add a quiet reminder in the bottom left that Santa follows the cursor
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        },
+        Text::new("Following cursor (F)"),
+        TextFont { font_size: 14.0, ..default() },
+        TextColor(Color::srgba(1.0, 1.0, 1.0, 0.5)),
+        if follow_cursor.0 { Visibility::Inherited } else { Visibility::Hidden },
+        UiFollowCursorText,
+        RunEntity,
+    ));
+    // Add the round clock in the top center
+    if *game_mode == GameMode::TimeAttack {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(10.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            RunEntity,
+        )).with_children(|parent| {
+            parent.spawn((Text::new(format_time(TIME_ATTACK_SECONDS)), UiRoundTimerText));
+        });
+    }
+    // Create Hearts, with the dash and stamina bars stacked below them
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::FlexEnd,
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        RunEntity,
+    )).with_children(|parent| {
+        let hearts_per_row = HEARTS_PER_ROW.min(starting_lives.0).max(1) as f32;
+        parent.spawn((
+            Node {
+                // Sized for a full row, so hearts beyond it wrap instead of stretching the HUD
+                width: Val::Px(hearts_per_row * 21.0 + (hearts_per_row - 1.0) * 5.0),
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::FlexEnd,
+                column_gap: Val::Px(5.0),
+                row_gap: Val::Px(5.0),
+                ..default()
+            },
+            UiHearts,
+        )).with_children(|parent| {
+            for i in 1..=starting_lives.0 {
+                spawn_heart(parent, &ui_images, i);
+            }
+        });
+        // The dash bar fills back up as the dash recharges
+        spawn_meter(parent, Color::srgb(0.4, 0.8, 1.0), UiDashBar);
+        spawn_meter(parent, Color::srgb(0.4, 1.0, 0.4), UiStaminaBar);
+        parent.spawn((
+            Node {
+                width: Val::Px(18.0),
+                height: Val::Px(18.0),
+                ..default()
+            },
+            ImageNode::new(ui_images.shield.clone()),
+            Visibility::Hidden,
+            UiShieldIndicator,
+        ));
+        parent.spawn((
+            Node {
+                width: Val::Px(18.0),
+                height: Val::Px(18.0),
+                ..default()
+            },
+            ImageNode::new(ui_images.boost.clone()),
+            Visibility::Hidden,
+            UiBoostIndicator,
+        ));
+    });
+    // Add the edge arrows, placed and shown by `edge_indicator_system`
+    for _ in 0..EDGE_INDICATOR_COUNT {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Px(EDGE_INDICATOR_SIZE),
+                height: Val::Px(EDGE_INDICATOR_SIZE),
+                ..default()
+            },
+            ImageNode::new(ui_images.arrow.clone()),
+            Visibility::Hidden,
+            UiEdgeIndicator,
+            RunEntity,
+        ));
+    }
+}
+
+const EDGE_INDICATOR_COUNT: usize = 3;
+const EDGE_INDICATOR_SIZE: f32 = 16.0;
+// How far the arrows sit from the window edges
+const EDGE_INDICATOR_INSET: f32 = 20.0;
+// Snowflakes within this middle part of the window are easy enough to see and get no arrow
+const EDGE_INDICATOR_CENTRAL_FRACTION: f32 = 0.5;
+
+// An arrow at the window edge pointing at one of the closest snowflakes
+#[derive(Component)]
+struct UiEdgeIndicator;
+
+// Where on the window edge the arrow goes, in UI coordinates, and the angle it points at in world coordinates.
+// None while the snowflake is inside the central region.
+fn edge_indicator_placement(size: Vec2, snowflake: Vec2) -> Option<(Vec2, f32)> {
+    let center = size / 2.0;
+    let offset = snowflake - center;
+    let central_half = size * EDGE_INDICATOR_CENTRAL_FRACTION / 2.0;
+    if offset.x.abs() <= central_half.x && offset.y.abs() <= central_half.y {
+        return None;
+    }
+    // Follow the line from the center towards the snowflake until it reaches the inset edge
+    let reach = (size / 2.0 - EDGE_INDICATOR_INSET).max(Vec2::ZERO);
+    let scale = (reach / offset.abs()).min_element();
+    let edge = center + offset * scale;
+    // UI coordinates start in the top left corner, the world ones in the bottom left
+    Some((Vec2::new(edge.x, size.y - edge.y), offset.to_angle()))
+}
+
+#[allow(clippy::type_complexity)]
+fn edge_indicator_system(
+    bounds: Res<GameBounds>,
+    santa: Query<&Transform, With<Santa>>,
+    snowflakes: Query<&Transform, With<Snowflake>>,
+    mut indicators: Query<(&mut Node, &mut Transform, &mut Visibility), (With<UiEdgeIndicator>, Without<Santa>, Without<Snowflake>)>,
+) {
+    let Ok(santa_transform) = santa.get_single() else {
+        return;
+    };
+    let santa_position = santa_transform.translation.truncate();
+    let size = bounds.size();
+    // The closest threats to Santa among the ones that are easy to miss
+    let mut placements: Vec<(f32, Vec2, f32)> = snowflakes
+        .iter()
+        .filter_map(|transform| {
+            let position = transform.translation.truncate();
+            edge_indicator_placement(size, position).map(|(edge, angle)| (position.distance_squared(santa_position), edge, angle))
+        })
+        .collect();
+    placements.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut placements = placements.into_iter();
+    for (mut node, mut transform, mut visibility) in indicators.iter_mut() {
+        let Some((_, edge, angle)) = placements.next() else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        node.left = Val::Px(edge.x - EDGE_INDICATOR_SIZE / 2.0);
+        node.top = Val::Px(edge.y - EDGE_INDICATOR_SIZE / 2.0);
+        // The UI y axis points down, so the world angle turns the other way
+        transform.rotation = Quat::from_rotation_z(-angle);
+        *visibility = Visibility::Inherited;
+    }
+}
+
+// A thin bar on a faint track, the marker goes on the fill whose width shows the value
+fn spawn_meter(parent: &mut ChildBuilder, color: Color, marker: impl Component) {
+    parent.spawn((
+        Node {
+            width: Val::Px(60.0),
+            height: Val::Px(6.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.2)),
+    )).with_children(|parent| {
+        parent.spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            BackgroundColor(color),
+            marker,
+        ));
+    });
+}
+
+// Presents are picked up first and only score once delivered to the sleigh
+fn pick_up_presents_system(
+    mut commands: Commands,
+    mut combo: ResMut<Combo>,
+    sound_effects: Res<SoundEffects>,
+    muted: Res<Muted>,
+    mut event_reader: EventReader<CollisionEvent>,
+    mut santa: Query<(&mut Carrying, &mut CarriedPoints), With<Santa>>,
+    point_values: Query<&PointValue>,
+) {
+    let (mut carrying, mut carried_points) = santa.single_mut();
+    for event in event_reader.read().filter(|event| event.kind.is_present()) {
+        // The present is only despawned in PostUpdate, so its value can still be looked up here
+        let points = point_values.get(event.entity).map_or(1, |point_value| point_value.0);
+        // Chaining pickups within the combo window raises the multiplier
+        if !combo.timer.finished() {
+            combo.count += 1;
+        }
+        combo.timer.reset();
+        // A present grabbed in the same frame Santa's arms fill up is dropped
+        carrying.0 = (carrying.0 + 1).min(MAX_CARRIED_PRESENTS);
+        carried_points.0 += points * combo.count;
+        play_sound_effect(&mut commands, &sound_effects.collect, &muted);
+        spawn_particle_burst(&mut commands, event.position, particle_color(event.kind));
+        spawn_floating_text(&mut commands, event.position, format!("+{}", points * combo.count));
+    }
+}
+
+const FLOATING_TEXT_SECONDS: f32 = 0.8;
+const FLOATING_TEXT_RISE: f32 = 40.0;
+
+// World space text that drifts away and fades out, then despawns
+#[derive(Component)]
+struct FloatingText {
+    velocity: Vec2,
+    timer: Timer,
+}
+
+fn spawn_floating_text(commands: &mut Commands, position: Vec2, text: String) {
+    commands.spawn((
+        RunEntity,
+        FloatingText {
+            velocity: Vec2::new(0.0, FLOATING_TEXT_RISE),
+            timer: Timer::from_seconds(FLOATING_TEXT_SECONDS, TimerMode::Once),
+        },
+        Text2d::new(text),
+        TextFont { font_size: 20.0, ..default() },
+        TextColor(Color::WHITE),
+        Transform::from_translation(position.extend(2.0)),
+    ));
+}
+
+fn animate_floating_text_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut texts: Query<(Entity, &mut FloatingText, &mut Transform, &mut TextColor)>,
+) {
+    for (entity, mut floating_text, mut transform, mut color) in texts.iter_mut() {
+        floating_text.timer.tick(time.delta());
+        if floating_text.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += (floating_text.velocity * time.delta_secs()).extend(0.0);
+        color.0.set_alpha(floating_text.timer.fraction_remaining());
+    }
+}
+
+const PARTICLE_COUNT: usize = 8;
+const PARTICLE_SPEED: f32 = 120.0;
+const PARTICLE_SECONDS: f32 = 0.4;
+const PARTICLE_SIZE: f32 = 4.0;
+
+// Velocity of a pickup particle in pixels per second
+#[derive(Component)]
+struct Particle(Vec2);
+
+// Despawns the entity once the timer runs out
+#[derive(Component)]
+struct Lifetime(Timer);
+
+fn particle_color(kind: CollisionKind) -> Color {
+    match kind {
+        CollisionKind::GoldPresent => Color::srgb(1.0, 0.84, 0.0),
+        _ => Color::srgb(1.0, 0.3, 0.3),
+    }
+}
+
+fn spawn_particle_burst(commands: &mut Commands, position: Vec2, color: Color) {
+    for i in 0..PARTICLE_COUNT {
+        // Evenly spread around the circle with a bit of jitter, so the burst does not look like a stamp
+        let angle = (i as f32 + fastrand::f32()) * std::f32::consts::TAU / PARTICLE_COUNT as f32;
+        let speed = PARTICLE_SPEED * (0.5 + fastrand::f32() * 0.5);
+        commands.spawn((
+            RunEntity,
+            Particle(Vec2::from_angle(angle) * speed),
+            Lifetime(Timer::from_seconds(PARTICLE_SECONDS, TimerMode::Once)),
+            Sprite::from_color(color, Vec2::splat(PARTICLE_SIZE)),
+            Transform::from_translation(position.extend(1.0)),
+        ));
+    }
+}
+
+fn particle_system(
+    time: Res<Time>,
+    mut particles: Query<(&mut Transform, &Particle)>,
+) {
+    for (mut transform, particle) in particles.iter_mut() {
+        transform.translation += (particle.0 * time.delta_secs()).extend(0.0);
+    }
+}
+
+fn lifetime_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut entities: Query<(Entity, &mut Lifetime, Option<&mut Sprite>)>,
+) {
+    for (entity, mut lifetime, sprite) in entities.iter_mut() {
+        lifetime.0.tick(time.delta());
+        if lifetime.0.finished() {
+            commands.entity(entity).despawn();
+        } else if let Some(mut sprite) = sprite {
+            // Fade out over the lifetime
+            sprite.color.set_alpha(lifetime.0.fraction_remaining());
+        }
+    }
+}
+
+fn score_points_system(
+    mut score: ResMut<Score>,
+    new_game_plus: Res<NewGamePlus>,
+    mut santa: Query<(&mut Carrying, &mut CarriedPoints), With<Santa>>,
+) {
+    let (mut carrying, mut carried_points) = santa.single_mut();
+    score.0 += carried_points.0 * new_game_plus.multiplier;
+    carrying.0 = 0;
+    carried_points.0 = 0;
+}
+
+#[derive(Resource)]
+struct SoundEffects {
+    collect: Handle<AudioSource>,
+    hit: Handle<AudioSource>,
+}
+
+fn load_sound_effects(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(SoundEffects {
+        collect: asset_server.load("collect.wav"),
+        hit: asset_server.load("hit.wav"),
+    });
+}
+
+// Every sound gets its own entity, so rapid collisions do not cut off each other
+fn play_sound_effect(commands: &mut Commands, sound: &Handle<AudioSource>, muted: &Muted) {
+    if muted.0 {
+        return;
+    }
+    commands.spawn((
+        AudioPlayer::new(sound.clone()),
+        PlaybackSettings::DESPAWN,
+    ));
+}
+
+// Whether all the game audio is silenced
+#[derive(Resource)]
+struct Muted(bool);
+
+#[derive(Component)]
+struct BackgroundMusic;
+
+fn setup_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.spawn((
+        AudioPlayer::new(asset_server.load("music.wav")),
+        PlaybackSettings::LOOP,
+        BackgroundMusic,
+    ));
+}
+
+fn toggle_mute_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut muted: ResMut<Muted>,
+    music: Query<&AudioSink, With<BackgroundMusic>>,
+) {
+    if keys.just_pressed(KeyCode::KeyM) {
+        muted.0 = !muted.0;
+    }
+    // The sink only appears once the music has loaded, so keep it in sync rather than only toggling on key press
+    for sink in music.iter() {
+        if muted.0 && !sink.is_paused() {
+            sink.pause();
+        } else if !muted.0 && sink.is_paused() {
+            sink.play();
+        }
+    }
+}
+
+// Seconds after a pickup in which the next one continues the combo
+const COMBO_WINDOW_SECONDS: f32 = 2.0;
+const COMBO_FADE_SECONDS: f32 = 0.5;
+
+#[derive(Resource)]
+struct Combo {
+    count: u32,
+    timer: Timer,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        // Start with an elapsed window so the first pickup does not extend anything
+        let mut timer = Timer::from_seconds(COMBO_WINDOW_SECONDS, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self { count: 1, timer }
+    }
+}
+
+#[derive(Component)]
+struct UiComboText;
+
+fn tick_combo_system(
+    time: Res<Time>,
+    mut combo: ResMut<Combo>,
+) {
+    if combo.timer.tick(time.delta()).just_finished() {
+        combo.count = 1;
+    }
+}
+
+fn update_combo_ui(
+    time: Res<Time>,
+    combo: Res<Combo>,
+    mut query: Query<(&mut Text, &mut TextColor), With<UiComboText>>,
+) {
+    let (mut text, mut color) = query.single_mut();
+    if combo.count > 1 {
+        text.0 = format!("Combo x{}", combo.count);
+        color.0.set_alpha(1.0);
+    } else {
+        // Keep showing the last multiplier while it fades out
+        let alpha = color.0.alpha() - time.delta_secs() / COMBO_FADE_SECONDS;
+        color.0.set_alpha(alpha.max(0.0));
+    }
+}
+
+fn score_label(score: u32, multiplier: u32) -> String {
+    if multiplier > 1 {
+        format!("Score: {} (x{})", score, multiplier)
+    } else {
+        format!("Score: {}", score)
+    }
+}
+
+fn update_score_ui(
+    score: Res<Score>,
+    new_game_plus: Res<NewGamePlus>,
+    mut query: Query<&mut Text, With<UiScoreText>>,
+) {
+    let mut text = query.single_mut();
+    text.0 = score_label(score.0, new_game_plus.multiplier);
+}
+
+fn update_carrying_ui(
+    santa: Query<&Carrying, (With<Santa>, Changed<Carrying>)>,
+    mut query: Query<&mut Text, With<UiCarryingText>>,
+) {
+    if let Ok(carrying) = santa.get_single() {
+        let mut text = query.single_mut();
+        text.0 = format!("Carrying: {}", carrying.0);
+    }
+}
+
+fn update_level_ui(
+    level: Res<Level>,
+    mut query: Query<&mut Text, With<UiLevelText>>,
+) {
+    let mut text = query.single_mut();
+    text.0 = format!("Level: {}", level.0);
+}
+
+fn update_timer_ui(
+    game_timer: Res<GameTimer>,
+    mut query: Query<&mut Text, With<UiTimerText>>,
+) {
+    let mut text = query.single_mut();
+    text.0 = format_time(game_timer.0.elapsed_secs());
+}
+
+const TIME_ATTACK_SECONDS: f32 = 60.0;
+const TIME_ATTACK_HIT_PENALTY: f32 = 5.0;
+// The round clock turns red when there is less time left than this
+const TIME_ATTACK_WARNING_SECONDS: f32 = 10.0;
+
+// Time left in a time attack round
+#[derive(Resource)]
+struct RoundTimer(Timer);
+
+fn tick_round_timer_system(
+    time: Res<Time>,
+    mut round_timer: ResMut<RoundTimer>,
+) {
+    round_timer.0.tick(time.delta());
+}
+
+fn update_round_timer_ui(
+    round_timer: Res<RoundTimer>,
+    mut query: Query<(&mut Text, &mut TextColor), With<UiRoundTimerText>>,
+) {
+    let (mut text, mut color) = query.single_mut();
+    let remaining = round_timer.0.remaining_secs();
+    // Round up, so the clock only shows 00:00 once the time is really up
+    text.0 = format_time(remaining.ceil());
+    color.0 = if remaining < TIME_ATTACK_WARNING_SECONDS { Color::srgb(1.0, 0.0, 0.0) } else { Color::WHITE };
+}
+
+fn round_over_system(
+    mut next_state: ResMut<NextState<GameState>>,
+    round_timer: Res<RoundTimer>,
+) {
+    if round_timer.0.finished() {
+        next_state.set(GameState::GameOver { won: true });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn take_lives_system(
+    mut commands: Commands,
+    mut lives: ResMut<Lives>,
+    game_mode: Res<GameMode>,
+    mut round_timer: ResMut<RoundTimer>,
+    sound_effects: Res<SoundEffects>,
+    muted: Res<Muted>,
+    mut screen_shake: ResMut<ScreenShake>,
+    mut event_reader: EventReader<CollisionEvent>,
+    mut santa: Query<(Entity, Has<Shield>, &mut Sprite), With<Santa>>,
+) {
+    let (santa, mut shielded, mut sprite) = santa.single_mut();
+    // Shield consumption lives in this very system, so one hit can never both break the shield and cost a life
+    for damage in event_reader.read().filter_map(|event| event.kind.damage()) {
+        if shielded {
+            // The shield takes the hit instead of Santa
+            shielded = false;
+            commands.entity(santa).remove::<Shield>().insert(ShieldBreak(Timer::from_seconds(SHIELD_BREAK_SECONDS, TimerMode::Once)));
+            sprite.color = Color::WHITE.with_alpha(sprite.color.alpha());
+            continue;
+        }
+        screen_shake.start();
+        commands.entity(santa).insert(Invulnerable(Timer::from_seconds(INVULNERABLE_SECONDS, TimerMode::Once)));
+        if *game_mode == GameMode::TimeAttack {
+            // Against the clock a hit costs time instead of a life
+            round_timer.0.tick(Duration::from_secs_f32(TIME_ATTACK_HIT_PENALTY * damage as f32));
+        } else {
+            lives.0 = lives.0.saturating_sub(damage);
+        }
+        play_sound_effect(&mut commands, &sound_effects.hit, &muted);
+    }
+}
+
+// Brings the displayed hearts in line with the lives, removing lost ones and adding back healed ones
+fn update_lives_ui(
+    mut commands: Commands,
+    ui_images: Res<UiImages>,
+    lives: Res<Lives>,
+    rows: Query<Entity, With<UiHearts>>,
+    hearts: Query<(Entity, &UiHeart)>,
+) {
+    let mut shown = 0;
+    for (entity, ui_heart) in hearts.iter() {
+        if ui_heart.0 > lives.0 {
+            commands.entity(entity).despawn();
+        } else {
+            shown = shown.max(ui_heart.0);
+        }
+    }
+    // Hearts are always lost from the end, so the missing ones are the ones past the last shown
+    for row in rows.iter() {
+        commands.entity(row).with_children(|parent| {
+            for i in shown + 1..=lives.0 {
+                spawn_heart(parent, &ui_images, i);
+            }
+        });
+    }
+}
+
+fn respawn_presents_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    santa: Query<&Transform, With<Santa>>,
+    mut event_reader: EventReader<CollisionEvent>,
+) {
+    let santa_position = santa.single().translation.truncate();
+    for event in event_reader.read() {
+        if event.kind.is_present() {
+            let scale = random_present_scale(&mut rng.0);
+            let position = random_spawn_position_away_from(&mut rng.0, &bounds, &free_zone, present_radius(scale), santa_position);
+            spawn_present(&mut commands, &asset_server, &mut rng.0, position, scale);
+        }
+    }
+}
+
+fn speed_up_on_score(
+    mut speed: ResMut<Speed>
+    , difficulty: Res<Difficulty>
+    , mut event_reader: EventReader<CollisionEvent>
+) {
+    for _ in event_reader.read().filter(|event| event.kind.is_present()) {
+        speed.0 += difficulty.speed_increment();
+    }
+}
+
+#[derive(Resource)]
+struct Level(u32);
+// Clearing this level wins the game
+#[derive(Resource)]
+struct FinalLevel(u32);
+
+const LEVEL_SPEED_BONUS: f32 = 50.0;
+
+// How a run is won. Clearing the board ends at the exit sleigh after the final level, reaching a
+// score ends the run right there and keeps the presents coming, so it works in every mode
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum WinCondition {
+    #[default]
+    ClearAll,
+    ReachScore(u32),
+}
+
+fn win_system(
+    mut next_state: ResMut<NextState<GameState>>,
+    win_condition: Res<WinCondition>,
+    score: Res<Score>,
+    mut event_reader: EventReader<CollisionEvent>,
+) {
+    let won = match *win_condition {
+        WinCondition::ClearAll => event_reader.read().any(|event| event.kind == CollisionKind::ExitPoint),
+        WinCondition::ReachScore(target) => score.0 >= target,
+    };
+    if won {
+        next_state.set(GameState::GameOver { won: true });
+    }
+}
+
+// Once everything is delivered on the final level the sleigh leaves its corner
+// and waits at an edge; the run is only won once Santa gets there
+#[derive(Component, Default)]
+struct ExitPoint;
+impl HasSpritePath for ExitPoint {
+    fn sprite_path() -> &'static str { "sleigh.png" }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_escape_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    mut rng: ResMut<GameRng>,
+    level: Res<Level>,
+    final_level: Res<FinalLevel>,
+    presents: Query<(), With<Present>>,
+    gold_presents: Query<(), With<GoldPresent>>,
+    santa: Query<(&Transform, &Carrying), With<Santa>>,
+    sleighs: Query<Entity, With<Sleigh>>,
+) {
+    let (santa_transform, carrying) = santa.single();
+    // Every present has to be delivered, not just picked up
+    let all_delivered = presents.is_empty() && gold_presents.is_empty() && carrying.0 == 0;
+    if !all_delivered || level.0 < final_level.0 {
+        return;
+    }
+    for sleigh in sleighs.iter() {
+        commands.entity(sleigh).despawn();
+    }
+    let position = random_edge_position_away_from(&mut rng.0, &bounds, santa_transform.translation.truncate());
+    commands.spawn((
+        RunEntity,
+        ExitPoint,
+        Transform::from_xyz(position.x, position.y, 0.0),
+        Sprite::from_image(asset_server.load(ExitPoint::sprite_path())),
+        ColliderCircle(24.),
+    ));
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Px(40.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        RunEntity,
+    )).with_children(|parent| {
+        parent.spawn(Text::new("Get to the sleigh!"));
+    });
+}
+
+// Once the board is cleared before the final level, move on to the next one with more of everything
+#[allow(clippy::too_many_arguments)]
+fn next_level_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bounds: Res<GameBounds>,
+    free_zone: Res<FreeZone>,
+    mut rng: ResMut<GameRng>,
+    difficulty: Res<Difficulty>,
+    mut level: ResMut<Level>,
+    final_level: Res<FinalLevel>,
+    mut speed: ResMut<Speed>,
+    mut max_snowflakes: ResMut<MaxSnowflakes>,
+    presents: Query<(), With<Present>>,
+    gold_presents: Query<(), With<GoldPresent>>,
+    santa: Query<&Carrying, With<Santa>>,
+) {
+    let all_delivered = presents.is_empty() && gold_presents.is_empty() && santa.single().0 == 0;
+    if !all_delivered || level.0 >= final_level.0 {
+        return;
+    }
+    level.0 += 1;
+    speed.0 += LEVEL_SPEED_BONUS;
+    let difficulty = *difficulty;
+    spawn_presents(&mut commands, &asset_server, &mut rng.0, &bounds, &free_zone, Present::spawn_count(difficulty, level.0));
+    // Snowflakes stay on the board, the extra ones for this level will spawn over time
+    max_snowflakes.0 = Snowflake::spawn_count(difficulty, level.0);
+}
+
+fn loose_system(
+    mut next_state: ResMut<NextState<GameState>>,
+    lives: Res<Lives>,
+) {
+    if lives.0 == 0 {
+        next_state.set(GameState::GameOver { won: false });
+    }
+}
+
+// Overlays the frozen game with the final result
+#[allow(clippy::too_many_arguments)]
+fn initialize_game_over_ui(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    high_score_beaten: Res<HighScoreBeaten>,
+    run_stats: Res<RunStats>,
+    run_grade: Res<RunGrade>,
+    new_record: Res<NewRecord>,
+    game_mode: Res<GameMode>,
+    new_game_plus: Res<NewGamePlus>,
+) {
+    let title = match (state.get(), *game_mode) {
+        (GameState::GameOver { won: true }, GameMode::TimeAttack) => "Time's up!",
+        (GameState::GameOver { won: true }, _) => "You Win!",
+        _ => "You Lose!",
+    };
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        StateScoped(*state.get()),
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new(title),
+            TextFont { font_size: 48.0, ..default() },
+        ));
+        if let Some(grade) = run_grade.0 {
+            parent.spawn((
+                Text::new(format!("Rank {}", grade.letter())),
+                TextFont { font_size: 96.0, ..default() },
+            ));
+        }
+        parent.spawn(Text::new(format!("Final score: {}", score.0)));
+        if high_score_beaten.0 {
+            parent.spawn(Text::new("New High Score!"));
+        }
+        if new_record.0 {
+            parent.spawn((Text::new("New record!"), Flashing));
+        }
+        parent.spawn(Text::new(format!("Presents collected: {}", run_stats.presents_collected)));
+        parent.spawn(Text::new(format!("Snowflake hits taken: {}", run_stats.hits_taken)));
+        parent.spawn(Text::new(format!("Time survived: {}", format_time(run_stats.time_survived))));
+        parent.spawn(Text::new(format!("Peak speed: {:.0}", run_stats.peak_speed)));
+        parent.spawn(Text::new(format!("Distance travelled: {:.0} px", run_stats.distance_travelled)));
+        if matches!((state.get(), *game_mode), (GameState::GameOver { won: true }, GameMode::Classic)) {
+            parent.spawn(Text::new(format!("Press N for New Game+ (x{} points)", new_game_plus.multiplier + 1)));
+        }
+        parent.spawn(Text::new("Press Enter or R to play again / Esc to quit"));
+    });
+}
+
+// Winning a Classic run can be followed by a harder one that keeps the speed and multiplies the points
+#[derive(Resource)]
+struct NewGamePlus {
+    multiplier: u32,
+    carried_speed: f32,
+    // Set when the player picks New Game+, consumed by the next run reset
+    pending: bool,
+}
+
+impl Default for NewGamePlus {
+    fn default() -> Self {
+        Self { multiplier: 1, carried_speed: 0.0, pending: false }
+    }
+}
+
+fn new_game_plus_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    speed: Res<Speed>,
+    mut new_game_plus: ResMut<NewGamePlus>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::KeyN) {
+        new_game_plus.multiplier += 1;
+        new_game_plus.carried_speed = speed.0;
+        new_game_plus.pending = true;
+        next_state.set(GameState::Countdown);
+    }
+}
+
+// Runs after `reset_run_system`; any run that was not started as New Game+ is a fresh game
+fn apply_new_game_plus_system(
+    mut new_game_plus: ResMut<NewGamePlus>,
+    mut speed: ResMut<Speed>,
+) {
+    if new_game_plus.pending {
+        new_game_plus.pending = false;
+        speed.0 = new_game_plus.carried_speed;
+    } else {
+        *new_game_plus = NewGamePlus::default();
+    }
+}
+
+fn game_over_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if keys.just_pressed(KeyCode::Enter) || gamepad_just_pressed(&gamepads, GamepadButton::South) {
+        next_state.set(GameState::Countdown);
+    } else if keys.just_pressed(KeyCode::Escape) {
+        exit.send(AppExit::Success);
+    }
+}
+
+// Best score of this session. Unlike `HighScore` it is not saved, and like it it survives run resets.
+#[derive(Resource, Default)]
+struct BestScore(u32);
+// Whether the run that just ended set a new session best
+#[derive(Resource, Default)]
+struct NewRecord(bool);
+
+// Returns whether the score beat the best one
+fn update_best_score(best_score: &mut BestScore, score: u32) -> bool {
+    if score > best_score.0 {
+        best_score.0 = score;
+        true
+    } else {
+        false
+    }
+}
+
+fn record_best_score_system(
+    score: Res<Score>,
+    mut best_score: ResMut<BestScore>,
+    mut new_record: ResMut<NewRecord>,
+) {
+    new_record.0 = update_best_score(&mut best_score, score.0);
+}
+
+#[derive(Component)]
+struct Flashing;
+
+fn flash_text_system(
+    time: Res<Time>,
+    mut query: Query<&mut Visibility, With<Flashing>>,
+) {
+    // Visible for half of every second
+    let visible = time.elapsed_secs().fract() < 0.5;
+    for mut visibility in query.iter_mut() {
+        *visibility = if visible { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+// Rank awarded for winning a Classic run. Variants are ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Grade {
+    S,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Grade {
+    fn letter(self) -> &'static str {
+        match self {
+            Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+        }
+    }
+    fn from_letter(letter: &str) -> Option<Grade> {
+        [Grade::S, Grade::A, Grade::B, Grade::C, Grade::D].into_iter().find(|grade| grade.letter() == letter)
+    }
+}
+
+// (grade, maximum lives lost, completion time must be under, maximum hits taken), best grade first.
+// Lives are counted against the starting stock, so every difficulty can reach an S.
+// A run that meets none of the rows gets a D.
+const GRADE_TABLE: [(Grade, u32, f32, u32); 4] = [
+    (Grade::S, 0, 30.0, 0),
+    (Grade::A, 1, 60.0, 1),
+    (Grade::B, u32::MAX, 90.0, 2),
+    (Grade::C, u32::MAX, 150.0, u32::MAX),
+];
+
+fn grade(lives: u32, starting_lives: u32, secs: f32, hits: u32) -> Grade {
+    let lives_lost = starting_lives.saturating_sub(lives);
+    GRADE_TABLE
+        .iter()
+        .find(|(_, max_lives_lost, max_secs, max_hits)| lives_lost <= *max_lives_lost && secs < *max_secs && hits <= *max_hits)
+        .map_or(Grade::D, |(grade, ..)| *grade)
+}
+
+// Grade of the run that just ended, if it earned one
+#[derive(Resource)]
+struct RunGrade(Option<Grade>);
+// The best grade ever achieved, persisted next to the high score
+#[derive(Resource)]
+struct BestGrade(Option<Grade>);
+
+const BEST_GRADE_FILE: &str = "bestgrade.txt";
+
+fn load_best_grade() -> Option<Grade> {
+    let content = std::fs::read_to_string(app_data_path(BEST_GRADE_FILE)?).ok()?;
+    Grade::from_letter(content.trim())
+}
+
+fn grade_run_system(
+    lives: Res<Lives>,
+    starting_lives: Res<StartingLives>,
+    run_stats: Res<RunStats>,
+    mut run_grade: ResMut<RunGrade>,
+    mut best_grade: ResMut<BestGrade>,
+) {
+    let grade = grade(lives.0, starting_lives.0, run_stats.time_survived, run_stats.hits_taken);
+    run_grade.0 = Some(grade);
+    if best_grade.0.is_none_or(|best| grade < best) {
+        best_grade.0 = Some(grade);
+        save_app_data(BEST_GRADE_FILE, grade.letter());
+    }
+}
+
+// The best score ever achieved, persisted in the app data directory between launches
+#[derive(Resource)]
+struct HighScore(u32);
+// Whether the current run has beaten the high score
+#[derive(Resource)]
+struct HighScoreBeaten(bool);
+
+fn app_data_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    base.map(|base| base.join("bevy-santa-demo"))
+}
+
+fn app_data_path(file_name: &str) -> Option<PathBuf> {
+    app_data_dir().map(|dir| dir.join(file_name))
+}
+
+fn save_app_data(file_name: &str, contents: &str) {
+    let Some(path) = app_data_path(file_name) else {
+        warn!("Could not determine the app data directory, {} will not be saved", file_name);
+        return;
+    };
+    let result = path.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, contents));
+    if let Err(error) = result {
+        warn!("Could not save {}: {}", path.display(), error);
+    }
+}
+
+const HIGH_SCORE_FILE: &str = "highscore.txt";
+
+fn load_high_score() -> u32 {
+    let Some(path) = app_data_path(HIGH_SCORE_FILE) else {
+        return 0;
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(content) => content.trim().parse().unwrap_or(0),
+        Err(_) => {
+            // First launch, create the file so it is there for the next run
+            save_high_score(0);
+            0
+        }
+    }
+}
+
+fn save_high_score(high_score: u32) {
+    save_app_data(HIGH_SCORE_FILE, &high_score.to_string());
+}
+
+fn update_high_score_system(
+    score: Res<Score>,
+    mut high_score: ResMut<HighScore>,
+    mut high_score_beaten: ResMut<HighScoreBeaten>,
+) {
+    if score.0 > high_score.0 {
+        high_score.0 = score.0;
+        high_score_beaten.0 = true;
+        save_high_score(high_score.0);
+    }
+}
+
+fn update_high_score_ui(
+    high_score: Res<HighScore>,
+    mut query: Query<&mut Text, With<UiHighScoreText>>,
+) {
+    let mut text = query.single_mut();
+    text.0 = format!("High score: {}", high_score.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    // The core gameplay loop without a window, rendering or audio, stepped one fixed frame at a time
+    struct Harness {
+        app: App,
+        santa: Entity,
+    }
+
+    impl Harness {
+        fn new(seed: u64) -> Self {
+            let mut app = App::new();
+            app.add_plugins(MinimalPlugins)
+                .insert_resource(GameRng::with_seed(seed))
+                .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1.0 / 60.0)))
+                .init_resource::<GameBounds>()
+                .insert_resource(FreeZone(Difficulty::default().free_zone()))
+                .insert_resource(Speed(Difficulty::default().speed()))
+                .init_resource::<SlowMo>()
+                .init_resource::<Frozen>()
+                .insert_resource(Score(0))
+                .init_resource::<Combo>()
+                .init_resource::<NewGamePlus>()
+                .insert_resource(CollisionFudge(1.0))
+                .init_resource::<SpatialGrid>()
+                .insert_resource(SoundEffects { collect: Handle::default(), hit: Handle::default() })
+                .insert_resource(Muted(true))
+                .add_event::<CollisionEvent>()
+                .add_systems(Update, (
+                    tick_combo_system,
+                    automoving_system,
+                    bounce_automovers_system,
+                    build_spatial_grid_system,
+                    (
+                        detect_collisions_system::<Present>.run_if(santa_has_room),
+                        detect_collisions_system::<GoldPresent>.run_if(santa_has_room),
+                        detect_collisions_system::<Sleigh>.run_if(santa_is_carrying),
+                    ),
+                    pick_up_presents_system.run_if(on_present_collision),
+                    score_points_system.run_if(on_collision(CollisionKind::Sleigh)),
+                ).chain())
+                .add_systems(PostUpdate, remove_entity_on_collission_system);
+            let center = app.world().resource::<GameBounds>().size() / 2.0;
+            let santa = app.world_mut().spawn((
+                Santa,
+                Transform::from_translation(center.extend(0.0)),
+                ColliderCircle(16.0),
+                Carrying(0),
+                CarriedPoints(0),
+            )).id();
+            Self { app, santa }
+        }
+
+        fn run_frames(&mut self, frames: usize) {
+            for _ in 0..frames {
+                self.app.update();
+            }
+        }
+
+        fn spawn_present(&mut self) -> Entity {
+            let bounds = *self.app.world().resource::<GameBounds>();
+            let mut rng = self.app.world_mut().resource_mut::<GameRng>();
+            let position = random_spawn_position(&mut rng.0, &bounds, &FreeZone(Difficulty::default().free_zone()), SPAWN_MARGIN);
+            let direction = random_direction(&mut rng.0);
+            self.app.world_mut().spawn((
+                Present,
+                Transform::from_translation(position.extend(0.0)),
+                ColliderCircle(16.0),
+                AutoMoving(direction),
+                PointValue(1),
+            )).id()
+        }
+
+        fn spawn_sleigh(&mut self) -> Entity {
+            let position = Vec2::new(self.app.world().resource::<GameBounds>().width - 48.0, 48.0);
+            self.app.world_mut().spawn((Sleigh, Transform::from_translation(position.extend(0.0)), ColliderCircle(24.0))).id()
+        }
+
+        fn place_santa_on(&mut self, entity: Entity) {
+            let translation = self.app.world().get::<Transform>(entity).unwrap().translation;
+            self.app.world_mut().get_mut::<Transform>(self.santa).unwrap().translation = translation;
+        }
+
+        fn score(&self) -> u32 {
+            self.app.world().resource::<Score>().0
+        }
+    }
+
+    #[test]
+    fn delivering_presents_raises_the_score() {
+        let mut harness = Harness::new(7);
+        let sleigh = harness.spawn_sleigh();
+        let presents: Vec<Entity> = (0..3).map(|_| harness.spawn_present()).collect();
+        harness.run_frames(10);
+
+        let mut last_score = harness.score();
+        assert_eq!(last_score, 0);
+        for present in presents {
+            harness.place_santa_on(present);
+            harness.run_frames(1);
+            assert!(harness.app.world().get_entity(present).is_err(), "the present is picked up");
+            harness.place_santa_on(sleigh);
+            harness.run_frames(1);
+            assert!(harness.score() > last_score);
+            last_score = harness.score();
+        }
+    }
+
+    #[test]
+    fn space_and_left_shift_dash_by_default() {
+        let bindings = InputBindings::default();
+        assert_eq!(bindings.keys(InputAction::Dash), &[KeyCode::Space, KeyCode::ShiftLeft]);
+        // No key may trigger two actions at once
+        for action in InputAction::ALL {
+            for other in InputAction::ALL.into_iter().filter(|&other| other != action) {
+                assert!(bindings.keys(action).iter().all(|key| !bindings.keys(other).contains(key)), "{action:?} and {other:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn diagonal_input_is_no_faster_than_a_single_direction() {
+        let movement_keys = MovementKeys(vec![InputAction::MoveUp, InputAction::MoveRight]);
+        let direction = PlayerInput { move_dir: movement_keys.direction(), ..default() }.direction();
+        assert!((direction.length() - 1.0).abs() < 1e-6);
+        assert!((direction.x - direction.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn player_input_direction_keeps_partial_tilts() {
+        assert_eq!(PlayerInput { move_dir: Vec2::new(0.5, 0.0), ..default() }.direction(), Vec2::new(0.5, 0.0));
+        assert_eq!(PlayerInput::default().direction(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn a_run_starting_unfocused_starts_paused() {
+        let mut world = World::new();
+        world.init_resource::<NextState<PauseState>>();
+        let window = world.spawn((Window::default(), PrimaryWindow)).id();
+
+        world.run_system_once(pause_if_unfocused_system).unwrap();
+        assert!(matches!(world.resource::<NextState<PauseState>>(), NextState::Unchanged));
+
+        world.get_mut::<Window>(window).unwrap().focused = false;
+        world.run_system_once(pause_if_unfocused_system).unwrap();
+        assert!(matches!(world.resource::<NextState<PauseState>>(), NextState::Pending(PauseState::Paused)));
+    }
+
+    #[test]
+    fn played_back_frames_restore_dash_and_move_target() {
+        let mut world = World::new();
+        world.init_resource::<MovementKeys>();
+        world.init_resource::<CursorWorldPosition>();
+        world.insert_resource(TimeUpdateStrategy::Automatic);
+        world.insert_resource(InputPlayback {
+            frames: vec![InputFrame {
+                delta: 0.016,
+                actions: vec![InputAction::Dash],
+                cursor: Some(Vec2::new(10.0, 20.0)),
+                move_target: Some(Vec2::new(300.0, 200.0)),
+            }],
+            next: 0,
+        });
+        let santa = world.spawn((Santa, MoveTarget(None))).id();
+        world.init_resource::<PlayerInput>();
+
+        world.run_system_once(play_back_input_system).unwrap();
+        world.run_system_once(read_keyboard_input_system).unwrap();
+
+        assert!(world.resource::<PlayerInput>().dash);
+        assert_eq!(world.resource::<CursorWorldPosition>().0, Some(Vec2::new(10.0, 20.0)));
+        assert_eq!(world.get::<MoveTarget>(santa).unwrap().0, Some(Vec2::new(300.0, 200.0)));
+    }
+
+    #[test]
+    fn santa_accelerates_along_the_player_input() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(100));
+        world.insert_resource(time);
+        world.insert_resource(PlayerInput { move_dir: Vec2::NEG_X, ..default() });
+        world.init_resource::<FollowCursor>();
+        world.init_resource::<CursorWorldPosition>();
+        world.insert_resource(SantaSpeed(100.0));
+        world.insert_resource(Acceleration(800.0));
+        world.insert_resource(Friction(600.0));
+        let santa = world.spawn((
+            Santa,
+            Transform::default(),
+            Velocity::default(),
+            Facing::default(),
+            Sprite::default(),
+            Carrying(0),
+            MoveTarget(None),
+            Stamina::default(),
+        )).id();
+
+        world.run_system_once(move_santa_system).unwrap();
+
+        let entity = world.entity(santa);
+        assert_eq!(entity.get::<Velocity>().unwrap().0, Vec2::new(-80.0, 0.0));
+        assert!(entity.get::<Facing>().unwrap() == &Facing::Left);
+    }
+
+    #[test]
+    fn grade_boundaries() {
+        let normal = Difficulty::Normal.lives();
+        let easy = Difficulty::Easy.lives();
+        let hard = Difficulty::Hard.lives();
+        // (lives left, starting lives, seconds, hits, expected grade)
+        for (lives, starting_lives, secs, hits, expected) in [
+            // A flawless fast win is an S
+            (normal, normal, 29.9, 0, Grade::S),
+            // S requires under 30 seconds
+            (normal, normal, 30.0, 0, Grade::A),
+            // S requires all lives, with no hits so that is the only condition missed
+            (normal - 1, normal, 10.0, 0, Grade::A),
+            // S requires no hits
+            (normal, normal, 10.0, 1, Grade::A),
+            // Every difficulty can reach an S with its full stock, and loses it with a single life less
+            (easy, easy, 29.9, 0, Grade::S),
+            (easy - 1, easy, 29.9, 0, Grade::A),
+            (hard, hard, 29.9, 0, Grade::S),
+            (hard - 1, hard, 29.9, 0, Grade::A),
+            (normal - 1, normal, 59.9, 1, Grade::A),
+            (normal - 1, normal, 60.0, 1, Grade::B),
+            (normal - 2, normal, 59.9, 1, Grade::B),
+            (1, normal, 89.9, 2, Grade::B),
+            (1, normal, 90.0, 2, Grade::C),
+            (1, normal, 149.9, 10, Grade::C),
+            (1, normal, 150.0, 10, Grade::D),
+        ] {
+            assert_eq!(grade(lives, starting_lives, secs, hits), expected, "{lives}/{starting_lives} lives, {secs}s, {hits} hits");
+        }
+    }
+
+    #[test]
+    fn spatial_grid_finds_the_same_collisions_as_a_naive_scan() {
+        use std::collections::HashSet;
+
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut world = World::new();
+        world.insert_resource(CollisionFudge(1.0));
+        world.init_resource::<SpatialGrid>();
+        world.init_resource::<Events<CollisionEvent>>();
+        for _ in 0..5000 {
+            let position = Vec2::new(rng.f32() * 1280.0, rng.f32() * 720.0);
+            world.spawn((
+                Snowflake,
+                Transform::from_xyz(position.x, position.y, 0.0),
+                AutoMoving(Vec2::X),
+                ColliderCircle(4.0 + rng.f32() * 20.0),
+            ));
+        }
+        let santa = world.spawn((Santa, Transform::default(), ColliderCircle(16.0))).id();
+
+        let mut total_collisions = 0;
+        for _ in 0..20 {
+            let santa_position = Vec2::new(rng.f32() * 1280.0, rng.f32() * 720.0);
+            world.entity_mut(santa).insert(Transform::from_xyz(santa_position.x, santa_position.y, 0.0));
+
+            let naive: HashSet<Entity> = world
+                .query_filtered::<(Entity, &Transform, &ColliderCircle), (With<Snowflake>, Without<Collided>)>()
+                .iter(&world)
+                .filter(|(_, transform, collider)| {
+                    circles_overlap(transform.translation.truncate(), collider.0, santa_position, 16.0, 1.0)
+                })
+                .map(|(entity, ..)| entity)
+                .collect();
+
+            world.run_system_once(build_spatial_grid_system).unwrap();
+            world.run_system_once(detect_collisions_system::<Snowflake>).unwrap();
+            let mut events = world.resource_mut::<Events<CollisionEvent>>();
+            let grid: HashSet<Entity> = events.drain().map(|event| event.entity).collect();
+
+            assert_eq!(grid, naive);
+            total_collisions += naive.len();
+        }
+        // Make sure the comparison was not trivially between empty sets
+        assert!(total_collisions > 0);
+    }
+
+    #[test]
+    fn lingering_snowflake_takes_exactly_one_life() {
+        let mut world = World::new();
+        world.insert_resource(CollisionFudge(1.0));
+        world.init_resource::<SpatialGrid>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.insert_resource(Lives(3));
+        world.insert_resource(GameMode::Classic);
+        world.insert_resource(RoundTimer(Timer::from_seconds(TIME_ATTACK_SECONDS, TimerMode::Once)));
+        world.insert_resource(SoundEffects { collect: Handle::default(), hit: Handle::default() });
+        world.insert_resource(Muted(true));
+        world.init_resource::<ScreenShake>();
+        world.spawn((Santa, Sprite::default(), Transform::default(), ColliderCircle(16.0)));
+        world.spawn((Snowflake, Transform::default(), ColliderCircle(8.0)));
+
+        // The snowflake is only despawned in PostUpdate, so it overlaps Santa for more than one detection pass
+        for _ in 0..3 {
+            world.run_system_once(build_spatial_grid_system).unwrap();
+            world.run_system_once(detect_collisions_system::<Snowflake>).unwrap();
+        }
+        world.run_system_once(take_lives_system).unwrap();
+
+        assert_eq!(world.resource::<Lives>().0, 2);
+    }
+
+    #[test]
+    fn hearts_follow_lives_up_and_down() {
+        let mut world = World::new();
+        world.insert_resource(UiImages { heart: Handle::default(), shield: Handle::default(), boost: Handle::default(), arrow: Handle::default() });
+        world.insert_resource(Lives(0));
+        world.spawn(UiHearts);
+
+        for lives in [3, 1, 0, 2, 3, 1, 5] {
+            world.resource_mut::<Lives>().0 = lives;
+            world.run_system_once(update_lives_ui).unwrap();
+            let mut hearts: Vec<u32> = world.query::<&UiHeart>().iter(&world).map(|heart| heart.0).collect();
+            hearts.sort();
+            assert_eq!(hearts, (1..=lives).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn zero_direction_sample_is_resampled() {
+        let mut samples = [Vec2::ZERO, Vec2::ZERO, Vec2::new(-0.3, 0.4)].into_iter();
+        let direction = direction_from_samples(|| samples.next().unwrap());
+        assert!(direction.is_finite());
+        assert!((direction.length() - 1.0).abs() < 1e-6);
+        assert_eq!(direction, Vec2::new(-0.6, 0.8));
+    }
+
+    #[test]
+    fn window_size_argument_is_parsed_and_validated() {
+        assert_eq!(parse_window_size("1024x768"), Ok(Vec2::new(1024.0, 768.0)));
+        assert_eq!(parse_window_size("320x240"), Ok(MIN_WINDOW_SIZE));
+        assert!(parse_window_size("319x240").is_err());
+        assert!(parse_window_size("640x200").is_err());
+        assert!(parse_window_size("640").is_err());
+        assert!(parse_window_size("widexhigh").is_err());
+    }
+
+    #[test]
+    fn board_is_cleared_once_no_present_is_left_anywhere() {
+        let mut world = World::new();
+        let santa = world.spawn((Santa, Carrying(0))).id();
+        let present = world.spawn(Present).id();
+        assert!(!world.run_system_once(board_cleared).unwrap());
+
+        // Taken by the reindeer, with Santa's hands empty
+        world.despawn(present);
+        assert!(world.run_system_once(board_cleared).unwrap());
+
+        world.entity_mut(santa).insert(Carrying(1));
+        assert!(!world.run_system_once(board_cleared).unwrap());
+        world.entity_mut(santa).insert(Carrying(0));
+        world.spawn(ExitPoint);
+        assert!(!world.run_system_once(board_cleared).unwrap());
+    }
+
+    #[test]
+    fn three_bombs_defeat_the_snowman() {
+        let mut world = World::new();
+        world.insert_resource(Score(0));
+        world.insert_resource(GameMode::Classic);
+        world.init_resource::<SpatialGrid>();
+        world.spawn((Santa, Transform::default()));
+        let snowman = world.spawn((Snowman { hp: SNOWMAN_HP }, Transform::from_xyz(100.0, 0.0, 0.0), ColliderCircle(24.0))).id();
+
+        for hp in (1..SNOWMAN_HP).rev() {
+            world.run_system_once(detonate_bomb_system).unwrap();
+            assert_eq!(world.get::<Snowman>(snowman).unwrap().hp, hp);
+        }
+        assert_eq!(world.resource::<Score>().0, 0);
+        world.run_system_once(detonate_bomb_system).unwrap();
+        assert!(world.get_entity(snowman).is_err());
+        assert_eq!(world.resource::<Score>().0, SNOWMAN_DEFEAT_POINTS);
+    }
+
+    #[test]
+    fn reindeer_trails_santa_by_the_follow_distance() {
+        let mut reindeer = Reindeer::new();
+        let mut follow_point = Vec2::ZERO;
+        for step in 0..=100 {
+            follow_point = reindeer.follow_point(Vec2::new(step as f32 * 2.0, 0.0));
+        }
+        assert_eq!(follow_point, Vec2::new(200.0 - REINDEER_FOLLOW_DISTANCE, 0.0));
+        // Standing still keeps the reindeer where it is
+        assert_eq!(reindeer.follow_point(Vec2::new(200.0, 0.0)), follow_point);
+    }
+
+    #[test]
+    fn camera_follows_the_bounds_to_the_center() {
+        let mut world = World::new();
+        world.insert_resource(GameBounds { width: 800.0, height: 600.0 });
+        let camera = world.spawn((Transform::from_xyz(400.0, 300.0, 0.0), CameraHome(Vec3::new(400.0, 300.0, 0.0)))).id();
+
+        world.insert_resource(GameBounds { width: 1600.0, height: 900.0 });
+        world.run_system_once(recenter_camera_system).unwrap();
+
+        let center = Vec3::new(800.0, 450.0, 0.0);
+        assert_eq!(world.get::<Transform>(camera).unwrap().translation, center);
+        assert_eq!(world.get::<CameraHome>(camera).unwrap().0, center);
+    }
+
+    #[test]
+    fn sleigh_hazard_flies_across_and_leaves_through_the_far_side() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(500));
+        world.insert_resource(time);
+        world.insert_resource(Speed(100.0));
+        world.insert_resource(GameBounds { width: 400.0, height: 300.0 });
+        let sleigh = world.spawn((SleighHazard { heading: -1.0 }, Transform::from_xyz(424.0, 150.0, 1.0))).id();
+
+        // Twice the automover speed, straight across at the same height
+        world.run_system_once(move_sleigh_hazard_system).unwrap();
+        assert_eq!(world.get::<Transform>(sleigh).unwrap().translation.truncate(), Vec2::new(324.0, 150.0));
+
+        // Instead of bouncing off the left edge it is gone once fully past it
+        world.entity_mut(sleigh).insert(Transform::from_xyz(100.0, 150.0, 1.0));
+        world.run_system_once(move_sleigh_hazard_system).unwrap();
+        assert!(world.get_entity(sleigh).is_ok());
+        world.run_system_once(move_sleigh_hazard_system).unwrap();
+        assert!(world.get_entity(sleigh).is_err());
+    }
+
+    #[test]
+    fn homing_turns_no_faster_than_its_turn_rate() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(100));
+        world.insert_resource(time);
+        world.spawn((Santa, Transform::from_xyz(0.0, 100.0, 0.0)));
+        let snowflake = world.spawn((AutoMoving(Vec2::X), Transform::default(), Homing { turn_rate: 1.0 })).id();
+
+        world.run_system_once(homing_system).unwrap();
+
+        // Santa is a quarter turn away, so only a tenth of a radian is covered
+        let direction = world.get::<AutoMoving>(snowflake).unwrap().0;
+        assert!((Vec2::X.angle_to(direction) - 0.1).abs() < 1e-5);
+        assert!((direction.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ice_patches_stay_clear_of_the_free_zone_and_edges() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        let free_zone = FreeZone(Difficulty::Easy.free_zone());
+        for size in [MIN_WINDOW_SIZE, Vec2::new(800.0, 600.0), Vec2::new(1920.0, 1080.0)] {
+            for _ in 0..200 {
+                let radius = ICE_PATCH_MAX_RADIUS;
+                let Some(position) = ice_patch_position(&mut rng, size, &free_zone, radius) else {
+                    continue;
+                };
+                assert!(position.distance(size / 2.0) - radius > free_zone.radius(size));
+                assert!(position.min_element() - radius >= ICE_EDGE_CORRIDOR);
+                assert!((size - position).min_element() - radius >= ICE_EDGE_CORRIDOR);
+            }
+        }
+    }
+
+    #[test]
+    fn trees_push_santa_out_and_bounce_automovers() {
+        let mut world = World::new();
+        world.spawn((Tree, Transform::from_xyz(100.0, 100.0, 0.0), ColliderCircle(TREE_RADIUS)));
+        // Santa walked a bit into the tree from the left
+        let santa = world.spawn((
+            Santa,
+            Transform::from_xyz(65.0, 100.0, 0.0),
+            Velocity(Vec2::new(200.0, 50.0)),
+            ColliderCircle(16.0),
+        )).id();
+        let snowflake = world.spawn((
+            AutoMoving(Vec2::NEG_Y),
+            Transform::from_xyz(100.0, 130.0, 0.0),
+            ColliderCircle(16.0),
+        )).id();
+
+        world.run_system_once(santa_tree_collision_system).unwrap();
+        world.run_system_once(bounce_off_trees_system).unwrap();
+
+        // Santa just touches the bark and only keeps sliding along it
+        assert_eq!(world.get::<Transform>(santa).unwrap().translation.truncate(), Vec2::new(60.0, 100.0));
+        assert_eq!(world.get::<Velocity>(santa).unwrap().0, Vec2::new(0.0, 50.0));
+        assert_eq!(world.get::<Transform>(snowflake).unwrap().translation.truncate(), Vec2::new(100.0, 140.0));
+        assert_eq!(world.get::<AutoMoving>(snowflake).unwrap().0, Vec2::Y);
+    }
+
+    #[test]
+    fn trees_keep_clear_of_the_free_zone_and_each_other() {
+        let mut rng = fastrand::Rng::with_seed(9);
+        let size = Vec2::new(1280.0, 720.0);
+        let free_zone = FreeZone(200.0);
+        let mut taken = vec![(Vec2::new(300.0, 200.0), 16.0)];
+        for _ in 0..8 {
+            let Some(position) = tree_position(&mut rng, size, &free_zone, &taken) else {
+                continue;
+            };
+            assert!(position.distance(size / 2.0) > 200.0 + TREE_RADIUS);
+            for &(other, radius) in &taken {
+                assert!(position.distance(other) > TREE_RADIUS + radius + TREE_GAP);
+            }
+            taken.push((position, TREE_RADIUS));
+        }
+        assert!(taken.len() > 1);
+    }
+
+    #[test]
+    fn edge_indicators_point_at_snowflakes_outside_the_center() {
+        let size = Vec2::new(800.0, 600.0);
+        // Within the middle half of the window there is no arrow
+        assert_eq!(edge_indicator_placement(size, Vec2::new(550.0, 400.0)), None);
+        // Straight right of the center the arrow sits on the right edge, pointing right
+        let (edge, angle) = edge_indicator_placement(size, Vec2::new(700.0, 300.0)).unwrap();
+        assert_eq!(edge, Vec2::new(780.0, 300.0));
+        assert_eq!(angle, 0.0);
+        // Near the top the arrow moves to the top edge, which is at the top of the UI too
+        let (edge, angle) = edge_indicator_placement(size, Vec2::new(400.0, 590.0)).unwrap();
+        assert_eq!(edge, Vec2::new(400.0, 20.0));
+        assert!((angle - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reaching_the_target_score_wins_the_run() {
+        let mut world = World::new();
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.insert_resource(WinCondition::ReachScore(10));
+        world.insert_resource(Score(9));
+        world.run_system_once(win_system).unwrap();
+        assert!(matches!(world.resource::<NextState<GameState>>(), NextState::Unchanged));
+
+        world.insert_resource(Score(10));
+        world.run_system_once(win_system).unwrap();
+        assert!(matches!(world.resource::<NextState<GameState>>(), NextState::Pending(GameState::GameOver { won: true })));
+        // The board can never be cleared, so the presents have to keep coming in any mode
+        assert!(GameMode::Classic.respawns_presents(WinCondition::ReachScore(10)));
+        assert!(!GameMode::Classic.respawns_presents(WinCondition::ClearAll));
+    }
+
+    #[test]
+    fn expired_presents_blink_and_then_relocate() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs(1));
+        world.insert_resource(time);
+        world.init_resource::<GameBounds>();
+        world.insert_resource(FreeZone(200.0));
+        world.insert_resource(GameRng::with_seed(11));
+        let santa_position = Vec2::new(100.0, 100.0);
+        world.spawn((Santa, Transform::from_translation(santa_position.extend(0.0))));
+        let mut lifetime = PresentLifetime::default();
+        lifetime.timer.set_elapsed(Duration::from_secs(17));
+        let present = world.spawn((
+            lifetime,
+            Transform::from_xyz(120.0, 100.0, 0.0),
+            AutoMoving(Vec2::X),
+            Visibility::Inherited,
+            ColliderCircle(present_radius(PRESENT_MAX_SCALE)),
+        )).id();
+
+        // Within the last three seconds it blinks but stays put
+        world.run_system_once(relocate_expired_presents_system).unwrap();
+        assert_eq!(world.get::<Visibility>(present), Some(&Visibility::Hidden));
+        assert_eq!(world.get::<Transform>(present).unwrap().translation.truncate(), Vec2::new(120.0, 100.0));
+
+        world.run_system_once(relocate_expired_presents_system).unwrap();
+        world.run_system_once(relocate_expired_presents_system).unwrap();
+        let position = world.get::<Transform>(present).unwrap().translation.truncate();
+        let bounds = *world.resource::<GameBounds>();
+        assert!(position.distance(bounds.size() / 2.0) > 200.0);
+        assert!(position.distance(santa_position) > SPAWN_DISTANCE_FROM_SANTA);
+        assert_ne!(world.get::<AutoMoving>(present).unwrap().0, Vec2::X);
+        assert_eq!(world.get::<Visibility>(present), Some(&Visibility::Inherited));
+        assert_eq!(world.get::<PresentLifetime>(present).unwrap().timer.elapsed_secs(), 0.0);
+    }
+
+    #[test]
+    fn smaller_presents_are_worth_more() {
+        assert_eq!(present_points(1, PRESENT_MIN_SCALE), 2);
+        assert_eq!(present_points(1, 1.0), 1);
+        assert_eq!(present_points(1, PRESENT_MAX_SCALE), 1);
+        assert_eq!(present_points(GOLD_PRESENT_POINTS, PRESENT_MIN_SCALE), 7);
+        assert_eq!(present_points(GOLD_PRESENT_POINTS, PRESENT_MAX_SCALE), 4);
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_layout() {
+        let layout = |seed| {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            (0..10).map(|_| (random_spawn_position_in(&mut rng, Vec2::new(800.0, 600.0), &FreeZone(200.0), SPAWN_MARGIN), random_direction(&mut rng))).collect::<Vec<_>>()
+        };
+        assert_eq!(layout(42), layout(42));
+        assert_ne!(layout(42), layout(43));
+    }
+
+    #[test]
+    fn spawned_automovers_stay_fully_on_screen() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let free_zone = FreeZone(Difficulty::Easy.free_zone());
+        for size in [MIN_WINDOW_SIZE, Vec2::new(480.0, 480.0), Vec2::new(800.0, 600.0), Vec2::new(1280.0, 720.0), Vec2::new(1920.0, 1080.0)] {
+            for _ in 0..1000 {
+                let position = random_spawn_position_in(&mut rng, size, &free_zone, SPAWN_MARGIN);
+                assert!(position.x >= SPAWN_MARGIN && position.x <= size.x - SPAWN_MARGIN, "{position} in {size}");
+                assert!(position.y >= SPAWN_MARGIN && position.y <= size.y - SPAWN_MARGIN, "{position} in {size}");
+                assert!(position.distance(size / 2.0) > free_zone.radius(size));
+            }
+            // Presents come in different sizes, each one has to fit with its own collider
+            for _ in 0..1000 {
+                let radius = present_radius(random_present_scale(&mut rng));
+                let position = random_spawn_position_in(&mut rng, size, &free_zone, radius);
+                assert!(position.x >= radius && position.x <= size.x - radius, "{position} with radius {radius} in {size}");
+                assert!(position.y >= radius && position.y <= size.y - radius, "{position} with radius {radius} in {size}");
+            }
+        }
+    }
+
+    #[test]
+    fn spawning_falls_back_to_a_corner_when_the_free_zone_covers_the_window() {
+        let mut rng = fastrand::Rng::with_seed(5);
+        let size = Vec2::new(40.0, 40.0);
+        let corners = [Vec2::new(16.0, 16.0), Vec2::new(16.0, 24.0), Vec2::new(24.0, 16.0), Vec2::new(24.0, 24.0)];
+        for _ in 0..20 {
+            let position = random_spawn_position_in(&mut rng, size, &FreeZone(1000.0), SPAWN_MARGIN);
+            assert!(corners.contains(&position), "{position}");
+        }
+        // Santa is close to every spot, so the spawns away from him settle for the farthest corner
+        let bounds = GameBounds { width: size.x, height: size.y };
+        let santa_position = Vec2::new(18.0, 18.0);
+        assert_eq!(random_spawn_position_away_from(&mut rng, &bounds, &FreeZone(1000.0), SPAWN_MARGIN, santa_position), Vec2::new(24.0, 24.0));
+        assert_eq!(random_edge_position_away_from(&mut rng, &bounds, santa_position), Vec2::new(24.0, 24.0));
+    }
+
+    #[test]
+    fn free_zone_never_exceeds_half_the_smaller_side() {
+        let free_zone = FreeZone(1000.0);
+        assert_eq!(free_zone.radius(Vec2::new(800.0, 600.0)), 300.0);
+        assert_eq!(free_zone.radius(MIN_WINDOW_SIZE), 120.0);
+        assert_eq!(FreeZone(Difficulty::Hard.free_zone()).radius(Vec2::new(1280.0, 720.0)), 160.0);
+    }
+
+    #[test]
+    fn higher_score_becomes_the_best() {
+        let mut best_score = BestScore(10);
+        assert!(update_best_score(&mut best_score, 11));
+        assert_eq!(best_score.0, 11);
+    }
+
+    #[test]
+    fn equal_or_lower_score_keeps_the_best() {
+        let mut best_score = BestScore(10);
+        assert!(!update_best_score(&mut best_score, 10));
+        assert!(!update_best_score(&mut best_score, 3));
+        assert_eq!(best_score.0, 10);
+    }
+
+    #[test]
+    fn grade_letters_round_trip() {
+        for grade in [Grade::S, Grade::A, Grade::B, Grade::C, Grade::D] {
+            assert_eq!(Grade::from_letter(grade.letter()), Some(grade));
+        }
+    }
+}